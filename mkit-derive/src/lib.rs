@@ -2,8 +2,9 @@ extern crate proc_macro;
 
 use lazy_static::lazy_static;
 use proc_macro2::TokenStream;
-use proc_macro_error::{abort_call_site, proc_macro_error};
+use proc_macro_error::{abort, abort_call_site, proc_macro_error};
 use quote::quote;
+use std::collections::HashSet;
 use syn::{spanned::Spanned, *};
 
 mod ty;
@@ -17,46 +18,66 @@ lazy_static! {
 #[proc_macro_error]
 pub fn cborize_type(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let input: DeriveInput = syn::parse(input).unwrap();
+    let crate_local = is_crate_local();
     let gen = match &input.data {
-        Data::Struct(_) => impl_cborize_struct(&input, false),
-        Data::Enum(_) => impl_cborize_enum(&input, false),
+        Data::Struct(_) => impl_cborize_struct(&input, crate_local),
+        Data::Enum(_) => impl_cborize_enum(&input, crate_local),
         Data::Union(_) => abort_call_site!("cannot derive Cborize for union"),
     };
     gen.into()
 }
 
+/// Deprecated alias for [`macro@Cborize`].
+///
+/// `Cborize` now detects crate-local expansion on its own (by checking
+/// whether it is being expanded inside the `mkit` crate itself), so this
+/// derive is no longer needed and will be removed in a future release.
 #[proc_macro_derive(LocalCborize, attributes(cbor))]
 #[proc_macro_error]
+#[deprecated(
+    since = "0.2.0",
+    note = "use `#[derive(Cborize)]`, crate-local expansion is now auto-detected"
+)]
 pub fn local_cborize_type(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
-    let input: DeriveInput = syn::parse(input).unwrap();
-    let gen = match &input.data {
-        Data::Struct(_) => impl_cborize_struct(&input, true),
-        Data::Enum(_) => impl_cborize_enum(&input, true),
-        Data::Union(_) => {
-            abort_call_site!("cannot derive LocalCborize for union")
-        }
-    };
-    gen.into()
+    cborize_type(input)
+}
+
+/// True when this derive is being expanded while compiling the `mkit`
+/// crate itself, in which case generated code must refer to `crate::...`
+/// instead of `::mkit::...`.
+fn is_crate_local() -> bool {
+    std::env::var("CARGO_CRATE_NAME")
+        .map(|name| name == "mkit")
+        .unwrap_or(false)
 }
 
 fn impl_cborize_struct(input: &DeriveInput, crate_local: bool) -> TokenStream {
     let name = &input.ident;
     let generics = no_default_generics(input);
+    let container_attrs = parse_container_attrs(&input.attrs);
+    let map_mode = container_attrs.map_mode;
+    let digest = schema_digest(input);
+    let fingerprint = container_attrs.fingerprint.then_some(digest);
 
     let mut ts = TokenStream::new();
     match &input.data {
         Data::Struct(ast) => {
+            ts.extend(schema_digest_const(name, &generics, digest));
             ts.extend(from_struct_to_cbor(
                 name,
                 &generics,
                 &ast.fields,
                 crate_local,
+                map_mode,
+                fingerprint,
             ));
             ts.extend(from_cbor_to_struct(
                 name,
                 &generics,
                 &ast.fields,
                 crate_local,
+                map_mode,
+                fingerprint,
             ));
             ts
         }
@@ -69,8 +90,10 @@ fn from_struct_to_cbor(
     generics: &Generics,
     fields: &Fields,
     crate_local: bool,
+    map_mode: bool,
+    fingerprint: Option<u64>,
 ) -> TokenStream {
-    let id_declr = let_id(name, generics);
+    let id_declr = let_id(name, generics, fingerprint);
     let croot = get_root_crate(crate_local);
     let preamble = quote! {
         let val: #croot::cbor::Cbor = {
@@ -82,9 +105,21 @@ fn from_struct_to_cbor(
 
     let token_fields = match fields {
         Fields::Unit => quote! {},
+        Fields::Named(fields) if map_mode => {
+            let push_fields = named_fields_into_map(fields, &croot);
+            quote! {
+                let mut map: Vec<(#croot::cbor::Key, #croot::cbor::Cbor)> = Vec::default();
+                #push_fields
+                items.push(map.into_cbor()?);
+            }
+        }
         Fields::Named(fields) => named_fields_to_cbor(fields, croot.clone()),
-        Fields::Unnamed(_) => {
-            abort_call_site!("unnamed struct not supported for Cborize {}", name)
+        Fields::Unnamed(fields) => {
+            let (params, body) = unnamed_fields_to_cbor(fields, croot.clone());
+            quote! {
+                let #name(#params) = value;
+                #body
+            }
         }
     };
 
@@ -120,18 +155,47 @@ fn from_cbor_to_struct(
     generics: &Generics,
     fields: &Fields,
     crate_local: bool,
+    map_mode: bool,
+    fingerprint: Option<u64>,
 ) -> TokenStream {
     let name_lit = name.to_string();
     let croot = get_root_crate(crate_local);
-    let n_fields = match fields {
-        Fields::Unit => 0,
-        Fields::Named(fields) => fields.named.len(),
-        Fields::Unnamed(_) => {
-            abort_call_site!("unnamed struct not supported for Cborize {}", name)
+    let is_map_named = map_mode && matches!(fields, Fields::Named(_));
+    let arity_check = match fields {
+        // a single Cbor::Map item follows the id tag instead of one item
+        // per field, so schema evolution doesn't change the arity.
+        Fields::Named(_) if is_map_named => quote! {
+            if 1 != items.len() {
+                #croot::err_at!(FailConvert, msg: "bad arity {} {}", 1, items.len())?;
+            }
+        },
+        Fields::Unit => quote! {
+            if 0 != items.len() {
+                #croot::err_at!(FailConvert, msg: "bad arity {} {}", 0, items.len())?;
+            }
+        },
+        // trailing `#[cbor(default)]` fields may be absent from an older
+        // message, so only the required-field count is a hard lower bound;
+        // extra trailing items from a newer message are simply dropped.
+        Fields::Named(fields) => {
+            let n_required = n_required_named_fields(fields);
+            quote! {
+                if items.len() < #n_required {
+                    #croot::err_at!(FailConvert, msg: "bad arity {} {}", #n_required, items.len())?;
+                }
+            }
+        }
+        Fields::Unnamed(fields) => {
+            let n_fields = fields.unnamed.len();
+            quote! {
+                if #n_fields != items.len() {
+                    #croot::err_at!(FailConvert, msg: "bad arity {} {}", #n_fields, items.len())?;
+                }
+            }
         }
     };
 
-    let id_declr = let_id(name, generics);
+    let id_declr = let_id(name, generics, fingerprint);
     let preamble = quote! {
         // validate the cbor msg for this type.
         if items.len() == 0 {
@@ -145,19 +209,29 @@ fn from_cbor_to_struct(
         if data_id != type_id {
             #croot::err_at!(FailConvert, msg: "bad id for {}", #name_lit)?;
         }
-        if #n_fields != items.len() {
-            #croot::err_at!(FailConvert, msg: "bad arity {} {}", #n_fields, items.len())?;
-        }
+        #arity_check
     };
 
-    let token_fields = match fields {
-        Fields::Unit => quote! {},
+    let construct = match fields {
+        Fields::Unit => quote! { Ok(#name) },
+        Fields::Named(fields) if is_map_named => {
+            let token_fields = map_to_named_fields(fields, &croot, &name_lit);
+            quote! {
+                {
+                    let mut map = Vec::<(#croot::cbor::Key, #croot::cbor::Cbor)>::from_cbor(
+                        items.remove(0),
+                    )?;
+                    Ok(#name { #token_fields })
+                }
+            }
+        }
         Fields::Named(fields) => {
             let token_fields = cbor_to_named_fields(fields, croot.clone());
-            quote! { { #token_fields } }
+            quote! { Ok(#name { #token_fields }) }
         }
-        Fields::Unnamed(_) => {
-            abort_call_site!("unnamed struct not supported for Cborize {}", name)
+        Fields::Unnamed(fields) => {
+            let (_, body) = cbor_to_unnamed_fields(fields, croot.clone());
+            quote! { Ok(#name ( #body )) }
         }
     };
 
@@ -182,7 +256,7 @@ fn from_cbor_to_struct(
 
                 #preamble
 
-                Ok(#name #token_fields)
+                #construct
             }
         }
     }
@@ -191,26 +265,98 @@ fn from_cbor_to_struct(
 fn impl_cborize_enum(input: &DeriveInput, crate_local: bool) -> TokenStream {
     let name = &input.ident;
     let generics = no_default_generics(input);
+    let fingerprint = parse_container_attrs(&input.attrs).fingerprint;
+    let digest = schema_digest(input);
+    let fingerprint = fingerprint.then_some(digest);
 
     let mut ts = TokenStream::new();
     match &input.data {
         Data::Enum(ast) => {
             let variants: Vec<&Variant> = ast.variants.iter().collect();
-            ts.extend(from_enum_to_cbor(name, &generics, &variants, crate_local));
-            ts.extend(from_cbor_to_enum(name, &generics, &variants, crate_local));
+            let tags = enum_variant_tags(&variants);
+            ts.extend(schema_digest_const(name, &generics, digest));
+            ts.extend(from_enum_to_cbor(
+                name, &generics, &variants, &tags, fingerprint, crate_local,
+            ));
+            ts.extend(from_cbor_to_enum(
+                name, &generics, &variants, &tags, fingerprint, crate_local,
+            ));
             ts
         }
         _ => unreachable!(),
     }
 }
 
+struct VariantAttrs {
+    tag: Option<u32>,
+}
+
+fn parse_variant_attrs(attrs: &[Attribute]) -> VariantAttrs {
+    let mut out = VariantAttrs { tag: None };
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("cbor")) {
+        let meta = match attr.parse_meta() {
+            Ok(meta) => meta,
+            Err(err) => abort!(attr, "invalid #[cbor(..)] attribute: {}", err),
+        };
+        let list = match meta {
+            Meta::List(list) => list,
+            _ => abort!(attr, "expected #[cbor(..)] to be a list attribute"),
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("tag") => {
+                    match &nv.lit {
+                        Lit::Int(lit) => match lit.base10_parse::<u32>() {
+                            Ok(val) => out.tag = Some(val),
+                            Err(err) => abort!(nv, "invalid #[cbor(tag = ..)] value: {}", err),
+                        },
+                        _ => abort!(nv, "#[cbor(tag = ..)] expects an integer"),
+                    }
+                }
+                _ => abort!(nested, "unsupported #[cbor(..)] variant attribute"),
+            }
+        }
+    }
+    out
+}
+
+// Either every variant carries an explicit `#[cbor(tag = ..)]`, in which
+// case the discriminant is encoded as a compact integer, or none do and
+// the variant name is encoded as a string, as before.
+fn enum_variant_tags(variants: &[&Variant]) -> Option<Vec<u32>> {
+    let tags: Vec<Option<u32>> = variants
+        .iter()
+        .map(|variant| parse_variant_attrs(&variant.attrs).tag)
+        .collect();
+    let n_tagged = tags.iter().filter(|tag| tag.is_some()).count();
+    if n_tagged == 0 {
+        return None;
+    }
+    if n_tagged != tags.len() {
+        let variant = variants[tags.iter().position(Option::is_none).unwrap()];
+        abort!(
+            variant,
+            "either all variants of this enum must carry #[cbor(tag = ..)] or none"
+        );
+    }
+    let mut seen = HashSet::new();
+    for (variant, tag) in variants.iter().zip(tags.iter()) {
+        if !seen.insert(tag.unwrap()) {
+            abort!(variant, "duplicate #[cbor(tag = {})] on enum variant", tag.unwrap());
+        }
+    }
+    Some(tags.into_iter().map(Option::unwrap).collect())
+}
+
 fn from_enum_to_cbor(
     name: &Ident,
     generics: &Generics,
     variants: &[&Variant],
+    tags: &Option<Vec<u32>>,
+    fingerprint: Option<u64>,
     crate_local: bool,
 ) -> TokenStream {
-    let id_declr = let_id(name, generics);
+    let id_declr = let_id(name, generics, fingerprint);
     let croot = get_root_crate(crate_local);
     let preamble = quote! {
         let val: #croot::cbor::Cbor = {
@@ -221,18 +367,25 @@ fn from_enum_to_cbor(
     };
 
     let mut tok_variants: TokenStream = TokenStream::new();
-    for variant in variants.iter() {
+    for (i, variant) in variants.iter().enumerate() {
         let variant_name = &variant.ident;
         let variant_lit = variant.ident.to_string();
+        let disc = match tags {
+            Some(tags) => {
+                let tag = tags[i];
+                quote! { (#tag as u32).into_cbor()? }
+            }
+            None => quote! { #variant_lit.into_cbor()? },
+        };
         let arm = match &variant.fields {
             Fields::Unit => {
-                quote! { #name::#variant_name => #variant_lit.into_cbor()? }
+                quote! { #name::#variant_name => #disc }
             }
             Fields::Named(fields) => {
                 let (params, body) = named_var_fields_to_cbor(fields, croot.clone());
                 quote! {
                     #name::#variant_name{#params} => {
-                        items.push(#variant_lit.into_cbor()?);
+                        items.push(#disc);
                         #body
                     },
                 }
@@ -241,7 +394,7 @@ fn from_enum_to_cbor(
                 let (params, body) = unnamed_fields_to_cbor(fields, croot.clone());
                 quote! {
                     #name::#variant_name(#params) => {
-                        items.push(#variant_lit.into_cbor()?);
+                        items.push(#disc);
                         #body
                     },
                 }
@@ -283,11 +436,17 @@ fn from_cbor_to_enum(
     name: &Ident,
     generics: &Generics,
     variants: &[&Variant],
+    tags: &Option<Vec<u32>>,
+    fingerprint: Option<u64>,
     crate_local: bool,
 ) -> TokenStream {
     let name_lit = name.to_string();
-    let id_declr = let_id(name, generics);
+    let id_declr = let_id(name, generics, fingerprint);
     let croot = get_root_crate(crate_local);
+    let discr_decl = match tags {
+        Some(_) => quote! { let variant_tag = u32::from_cbor(items.remove(0))?; },
+        None => quote! { let variant_name = String::from_cbor(items.remove(0))?; },
+    };
     let preamble = quote! {
         // validate the cbor msg for this type.
         if items.len() < 2 {
@@ -302,21 +461,31 @@ fn from_cbor_to_enum(
             #croot::err_at!(FailConvert, msg: "bad {}", #name_lit)?
         }
 
-        let variant_name = String::from_cbor(items.remove(0))?;
+        #discr_decl
     };
 
     let mut check_variants: TokenStream = TokenStream::new();
-    for variant in variants.iter() {
+    for (i, variant) in variants.iter().enumerate() {
         let variant_lit = &variant.ident.to_string();
+        let pattern = match tags {
+            Some(tags) => {
+                let tag = tags[i];
+                quote! { #tag }
+            }
+            None => quote! { #variant_lit },
+        };
         let arm = match &variant.fields {
             Fields::Named(fields) => {
-                let n_fields = fields.named.len();
+                // trailing `#[cbor(default)]` fields may be absent from an
+                // older message; extra trailing items from a newer message
+                // are simply dropped by the constructor below.
+                let n_required = n_required_named_fields(fields);
                 quote! {
-                   #variant_lit => {
-                        if #n_fields != items.len() {
+                   #pattern => {
+                        if items.len() < #n_required {
                             #croot::err_at!(
                                 FailConvert, msg: "bad arity {} {}",
-                                #n_fields, items.len()
+                                #n_required, items.len()
                             )?;
                         }
                     }
@@ -325,7 +494,7 @@ fn from_cbor_to_enum(
             Fields::Unnamed(fields) => {
                 let n_fields = fields.unnamed.len();
                 quote! {
-                    #variant_lit => {
+                    #pattern => {
                         if #n_fields != items.len() {
                             #croot::err_at!(
                                 FailConvert, msg: "bad arity {} {}",
@@ -337,7 +506,7 @@ fn from_cbor_to_enum(
             }
             Fields::Unit => {
                 quote! {
-                    #variant_lit => {
+                    #pattern => {
                         if items.len() > 0 {
                             #croot::err_at!(
                                 FailConvert, msg: "bad arity {}", items.len()
@@ -351,20 +520,27 @@ fn from_cbor_to_enum(
     }
 
     let mut tok_variants: TokenStream = TokenStream::new();
-    for variant in variants.iter() {
+    for (i, variant) in variants.iter().enumerate() {
         let variant_name = &variant.ident;
         let variant_lit = &variant.ident.to_string();
+        let pattern = match tags {
+            Some(tags) => {
+                let tag = tags[i];
+                quote! { #tag }
+            }
+            None => quote! { #variant_lit },
+        };
         let arm = match &variant.fields {
             Fields::Unit => quote! {
-                #variant_lit => #name::#variant_name
+                #pattern => #name::#variant_name
             },
             Fields::Named(fields) => {
                 let (_, body) = cbor_to_named_var_fields(fields, croot.clone());
-                quote! { #variant_lit => #name::#variant_name { #body }, }
+                quote! { #pattern => #name::#variant_name { #body }, }
             }
             Fields::Unnamed(fields) => {
                 let (_, body) = cbor_to_unnamed_fields(fields, croot.clone());
-                quote! { #variant_lit => #name::#variant_name(#body), }
+                quote! { #pattern => #name::#variant_name(#body), }
             }
         };
         tok_variants.extend(arm);
@@ -381,6 +557,16 @@ fn from_cbor_to_enum(
         };
         where_clause.extend(quote! { #type_var: #croot::cbor::FromCbor, });
     }
+
+    let discr_expr = match tags {
+        Some(_) => quote! { variant_tag },
+        None => quote! { variant_name.as_str() },
+    };
+    let invalid_msg = match tags {
+        Some(_) => quote! { "invalid variant_tag {}", variant_tag },
+        None => quote! { "invalid variant_name {}", variant_name },
+    };
+
     quote! {
         impl#generics #croot::cbor::FromCbor for #name#generics #where_clause {
             fn from_cbor(value: #croot::cbor::Cbor) -> #croot::Result<#name#generics> {
@@ -390,18 +576,14 @@ fn from_cbor_to_enum(
 
                 #preamble
 
-                match variant_name.as_str() {
+                match #discr_expr {
                     #check_variants
-                    _ => #croot::err_at!(
-                        FailConvert, msg: "invalid variant_name {}", variant_name
-                    )?,
+                    _ => #croot::err_at!(FailConvert, msg: #invalid_msg)?,
                 }
 
-                let val = match variant_name.as_str() {
+                let val = match #discr_expr {
                     #tok_variants
-                    _ => #croot::err_at!(
-                        FailConvert, msg: "invalid variant_name {}", variant_name
-                    )?,
+                    _ => #croot::err_at!(FailConvert, msg: #invalid_msg)?,
                 };
                 Ok(val)
             }
@@ -412,6 +594,9 @@ fn from_cbor_to_enum(
 fn named_fields_to_cbor(fields: &FieldsNamed, croot: TokenStream) -> TokenStream {
     let mut tokens = TokenStream::new();
     for field in fields.named.iter() {
+        if !is_encoded_named_field(field) {
+            continue;
+        }
         let is_bytes = is_bytes_ty(&field.ty);
 
         match &field.ident {
@@ -434,11 +619,14 @@ fn named_var_fields_to_cbor(
     let mut params = TokenStream::new();
     let mut body = TokenStream::new();
     for field in fields.named.iter() {
-        let is_bytes = is_bytes_ty(&field.ty);
-
         let field_name = field.ident.as_ref().unwrap();
         params.extend(quote! { #field_name, });
 
+        if !is_encoded_named_field(field) {
+            continue;
+        }
+        let is_bytes = is_bytes_ty(&field.ty);
+
         match &field.ident {
             Some(field_name) if is_bytes => body.extend(quote! {
                 items.push(#croot::cbor::Cbor::bytes_into_cbor(#field_name)?);
@@ -480,18 +668,31 @@ fn unnamed_fields_to_cbor(
 fn cbor_to_named_fields(fields: &FieldsNamed, croot: TokenStream) -> TokenStream {
     let mut tokens = TokenStream::new();
     for field in fields.named.iter() {
-        let is_bytes = is_bytes_ty(&field.ty);
-
+        let attrs = parse_field_attrs(&field.attrs);
         let field_name = field.ident.as_ref().unwrap();
+
+        if attrs.skip {
+            tokens.extend(quote! { #field_name: ::std::default::Default::default(), });
+            continue;
+        }
+
+        let is_bytes = is_bytes_ty(&field.ty);
         let ty = &field.ty;
-        let field_tokens = if is_bytes {
-            quote! {
-                #field_name: items.remove(0).into_bytes()?,
-            }
+        let decode = if is_bytes {
+            quote! { items.remove(0).into_bytes()? }
         } else {
+            quote! { <#ty as #croot::cbor::FromCbor>::from_cbor(items.remove(0))? }
+        };
+        let field_tokens = if attrs.default {
             quote! {
-                #field_name: <#ty as #croot::cbor::FromCbor>::from_cbor(items.remove(0))?,
+                #field_name: if items.is_empty() {
+                    ::std::default::Default::default()
+                } else {
+                    #decode
+                },
             }
+        } else {
+            quote! { #field_name: #decode, }
         };
         tokens.extend(field_tokens);
     }
@@ -505,20 +706,32 @@ fn cbor_to_named_var_fields(
     let mut params = TokenStream::new();
     let mut body = TokenStream::new();
     for field in fields.named.iter() {
-        let is_bytes = is_bytes_ty(&field.ty);
-
+        let attrs = parse_field_attrs(&field.attrs);
         let field_name = field.ident.as_ref().unwrap();
         params.extend(quote! { #field_name, });
 
+        if attrs.skip {
+            body.extend(quote! { #field_name: ::std::default::Default::default(), });
+            continue;
+        }
+
+        let is_bytes = is_bytes_ty(&field.ty);
         let ty = &field.ty;
-        if is_bytes {
-            body.extend(quote! {
-                #field_name: items.remove(0).into_bytes()?,
-            });
+        let decode = if is_bytes {
+            quote! { items.remove(0).into_bytes()? }
         } else {
+            quote! { <#ty as #croot::cbor::FromCbor>::from_cbor(items.remove(0))? }
+        };
+        if attrs.default {
             body.extend(quote! {
-                #field_name: <#ty as #croot::cbor::FromCbor>::from_cbor(items.remove(0))?,
+                #field_name: if items.is_empty() {
+                    ::std::default::Default::default()
+                } else {
+                    #decode
+                },
             });
+        } else {
+            body.extend(quote! { #field_name: #decode, });
         }
     }
     (params, body)
@@ -548,11 +761,20 @@ fn cbor_to_unnamed_fields(
     (params, body)
 }
 
-fn let_id(name: &Ident, generics: &Generics) -> TokenStream {
-    if generics.params.is_empty() {
-        quote! { let id = #name::ID.into_cbor()? }
+fn let_id(name: &Ident, generics: &Generics, fingerprint: Option<u64>) -> TokenStream {
+    let id_expr = if generics.params.is_empty() {
+        quote! { #name::ID.into_cbor()? }
     } else {
-        quote! { let id = #name::#generics::ID.into_cbor()? }
+        quote! { #name::#generics::ID.into_cbor()? }
+    };
+    match fingerprint {
+        // fold the structural digest into the wire id so peers compiling
+        // structurally different versions of the type fail the
+        // `data_id != type_id` check instead of silently misparsing.
+        Some(digest) => quote! {
+            let id = vec![#id_expr, #digest.into_cbor()?].into_cbor()?
+        },
+        None => quote! { let id = #id_expr },
     }
 }
 
@@ -582,3 +804,573 @@ fn is_bytes_ty(ty: &syn::Type) -> bool {
         None => false,
     }
 }
+
+/// Derive [`mkit::cbor::IntoCbor`] for a struct or enum, mapping a struct to
+/// a `Major5` map keyed by `Key::Text(field_name)` and an enum to a
+/// single-entry `Major5` map keyed by the variant name. Fields accept a
+/// `#[cbor(rename = "...")]` / `#[cbor(skip)]` attribute set.
+#[proc_macro_derive(IntoCbor, attributes(cbor))]
+#[proc_macro_error]
+pub fn into_cbor_type(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input: DeriveInput = syn::parse(input).unwrap();
+    let gen = match &input.data {
+        Data::Struct(ast) => into_cbor_for_struct(&input, &ast.fields),
+        Data::Enum(ast) => {
+            let variants: Vec<&Variant> = ast.variants.iter().collect();
+            into_cbor_for_enum(&input, &variants)
+        }
+        Data::Union(_) => abort_call_site!("cannot derive IntoCbor for union"),
+    };
+    gen.into()
+}
+
+/// Derive [`mkit::cbor::FromCbor`] for a struct or enum, the inverse of
+/// [`macro@IntoCbor`]. A missing field is reported as a `FailConvert`
+/// error, unless it is `#[cbor(skip)]` or `#[cbor(default)]`, in which
+/// case it falls back to `Default::default()`.
+#[proc_macro_derive(FromCbor, attributes(cbor))]
+#[proc_macro_error]
+pub fn from_cbor_type(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input: DeriveInput = syn::parse(input).unwrap();
+    let gen = match &input.data {
+        Data::Struct(ast) => from_cbor_for_struct(&input, &ast.fields),
+        Data::Enum(ast) => {
+            let variants: Vec<&Variant> = ast.variants.iter().collect();
+            from_cbor_for_enum(&input, &variants)
+        }
+        Data::Union(_) => abort_call_site!("cannot derive FromCbor for union"),
+    };
+    gen.into()
+}
+
+struct ContainerAttrs {
+    map_mode: bool,
+    fingerprint: bool,
+}
+
+fn parse_container_attrs(attrs: &[Attribute]) -> ContainerAttrs {
+    let mut out = ContainerAttrs { map_mode: false, fingerprint: false };
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("cbor")) {
+        let meta = match attr.parse_meta() {
+            Ok(meta) => meta,
+            Err(err) => abort!(attr, "invalid #[cbor(..)] attribute: {}", err),
+        };
+        let list = match meta {
+            Meta::List(list) => list,
+            _ => abort!(attr, "expected #[cbor(..)] to be a list attribute"),
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("map") => {
+                    out.map_mode = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("fingerprint") => {
+                    out.fingerprint = true;
+                }
+                _ => abort!(nested, "unsupported #[cbor(..)] container attribute"),
+            }
+        }
+    }
+    out
+}
+
+// A canonical, order-sensitive description of a type's field/variant
+// layout: field names (or positional indices) paired with their
+// spelled-out types, and for enums, variant names and arities. Two crates
+// that compile the same source see the same string, and hence the same
+// digest.
+fn canonical_schema_string(input: &DeriveInput) -> String {
+    let mut out = input.ident.to_string();
+    match &input.data {
+        Data::Struct(data) => {
+            out.push_str(";struct");
+            push_canonical_fields(&mut out, &data.fields);
+        }
+        Data::Enum(data) => {
+            out.push_str(";enum");
+            for variant in data.variants.iter() {
+                out.push_str(&format!(";variant={}", variant.ident));
+                push_canonical_fields(&mut out, &variant.fields);
+            }
+        }
+        Data::Union(_) => abort_call_site!("cannot derive a schema digest for union"),
+    }
+    out
+}
+
+fn push_canonical_fields(out: &mut String, fields: &Fields) {
+    match fields {
+        Fields::Unit => out.push_str(";unit"),
+        Fields::Named(fields) => {
+            for field in fields.named.iter() {
+                let field_name = field.ident.as_ref().unwrap();
+                let ty = &field.ty;
+                let ty_lit = quote! { #ty }.to_string();
+                out.push_str(&format!(";{}:{}", field_name, ty_lit));
+            }
+        }
+        Fields::Unnamed(fields) => {
+            for (i, field) in fields.unnamed.iter().enumerate() {
+                let ty = &field.ty;
+                let ty_lit = quote! { #ty }.to_string();
+                out.push_str(&format!(";{}:{}", i, ty_lit));
+            }
+        }
+    }
+}
+
+fn schema_digest(input: &DeriveInput) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    canonical_schema_string(input).hash(&mut hasher);
+    hasher.finish()
+}
+
+// Emit `CBOR_SCHEMA_DIGEST`, an inherent const capturing the structural
+// fingerprint, so peers can compare it out-of-band (or, with
+// `#[cbor(fingerprint)]`, it's folded into the wire type-id automatically).
+fn schema_digest_const(name: &Ident, generics: &Generics, digest: u64) -> TokenStream {
+    let where_clause = &generics.where_clause;
+    quote! {
+        impl#generics #name#generics #where_clause {
+            /// A structural fingerprint of this type's field/variant layout,
+            /// computed at macro-expansion time from field names, their
+            /// spelled-out types, and (for enums) variant names and
+            /// arities. Two crates that agree on the source agree on this
+            /// digest, so a mismatch signals incompatible schema drift.
+            pub const CBOR_SCHEMA_DIGEST: u64 = #digest;
+        }
+    }
+}
+
+struct FieldAttrs {
+    rename: Option<String>,
+    skip: bool,
+    default: bool,
+}
+
+fn parse_field_attrs(attrs: &[Attribute]) -> FieldAttrs {
+    let mut out = FieldAttrs {
+        rename: None,
+        skip: false,
+        default: false,
+    };
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("cbor")) {
+        let meta = match attr.parse_meta() {
+            Ok(meta) => meta,
+            Err(err) => abort!(attr, "invalid #[cbor(..)] attribute: {}", err),
+        };
+        let list = match meta {
+            Meta::List(list) => list,
+            _ => abort!(attr, "expected #[cbor(..)] to be a list attribute"),
+        };
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                    match &nv.lit {
+                        Lit::Str(lit) => out.rename = Some(lit.value()),
+                        _ => abort!(nv, "#[cbor(rename = \"..\")] expects a string"),
+                    }
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                    out.skip = true;
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => {
+                    out.default = true;
+                }
+                _ => abort!(nested, "unsupported #[cbor(..)] attribute"),
+            }
+        }
+    }
+    out
+}
+
+// A field counts towards the strict, lower-bound arity check only when
+// decoding it can't fall back to `Default::default()`.
+fn is_required_named_field(field: &Field) -> bool {
+    let attrs = parse_field_attrs(&field.attrs);
+    !attrs.skip && !attrs.default
+}
+
+// `#[cbor(skip)]` fields are never pushed onto `items`, so they don't
+// count towards the arity in either direction.
+fn is_encoded_named_field(field: &Field) -> bool {
+    !parse_field_attrs(&field.attrs).skip
+}
+
+fn n_required_named_fields(fields: &FieldsNamed) -> usize {
+    fields.named.iter().filter(|field| is_required_named_field(field)).count()
+}
+
+fn field_key_lit(field: &Field, attrs: &FieldAttrs) -> String {
+    attrs
+        .rename
+        .clone()
+        .unwrap_or_else(|| field.ident.as_ref().unwrap().to_string())
+}
+
+fn into_cbor_where_clause(generics: &Generics, croot: &TokenStream) -> TokenStream {
+    let mut where_clause = match &generics.where_clause {
+        Some(where_clause) => quote! { #where_clause },
+        None => quote! { where },
+    };
+    for param in generics.params.iter() {
+        let type_var = match param {
+            GenericParam::Type(param) => &param.ident,
+            _ => abort_call_site!("only type parameter are supported"),
+        };
+        where_clause.extend(quote! { #type_var: #croot::cbor::IntoCbor, });
+    }
+    where_clause
+}
+
+fn from_cbor_where_clause(generics: &Generics, croot: &TokenStream) -> TokenStream {
+    let mut where_clause = match &generics.where_clause {
+        Some(where_clause) => quote! { #where_clause },
+        None => quote! { where },
+    };
+    for param in generics.params.iter() {
+        let type_var = match param {
+            GenericParam::Type(param) => &param.ident,
+            _ => abort_call_site!("only type parameter are supported"),
+        };
+        where_clause.extend(quote! { #type_var: #croot::cbor::FromCbor, });
+    }
+    where_clause
+}
+
+fn named_fields_into_map(fields: &FieldsNamed, croot: &TokenStream) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    for field in fields.named.iter() {
+        let attrs = parse_field_attrs(&field.attrs);
+        if attrs.skip {
+            continue;
+        }
+        let key_lit = field_key_lit(field, &attrs);
+        let field_name = field.ident.as_ref().unwrap();
+        let value = if is_bytes_ty(&field.ty) {
+            quote! { #croot::cbor::Cbor::bytes_into_cbor(value.#field_name)? }
+        } else {
+            quote! { value.#field_name.into_cbor()? }
+        };
+        tokens.extend(quote! {
+            map.push((#croot::cbor::Key::Text(#key_lit.to_string()), #value));
+        });
+    }
+    tokens
+}
+
+fn named_var_fields_into_map(
+    fields: &FieldsNamed,
+    croot: &TokenStream,
+) -> (TokenStream, TokenStream) {
+    let mut params = TokenStream::new();
+    let mut body = TokenStream::new();
+    for field in fields.named.iter() {
+        let field_name = field.ident.as_ref().unwrap();
+        params.extend(quote! { #field_name, });
+
+        let attrs = parse_field_attrs(&field.attrs);
+        if attrs.skip {
+            continue;
+        }
+        let key_lit = field_key_lit(field, &attrs);
+        let value = if is_bytes_ty(&field.ty) {
+            quote! { #croot::cbor::Cbor::bytes_into_cbor(#field_name)? }
+        } else {
+            quote! { #field_name.into_cbor()? }
+        };
+        body.extend(quote! {
+            map.push((#croot::cbor::Key::Text(#key_lit.to_string()), #value));
+        });
+    }
+    (params, body)
+}
+
+fn unnamed_fields_into_items(
+    fields: &FieldsUnnamed,
+    croot: &TokenStream,
+) -> (TokenStream, TokenStream) {
+    let mut params = TokenStream::new();
+    let mut body = TokenStream::new();
+    for (field_name, field) in UNNAMED_FIELDS.iter().zip(fields.unnamed.iter()) {
+        let field_name = Ident::new(field_name, field.span());
+        params.extend(quote! { #field_name, });
+
+        if is_bytes_ty(&field.ty) {
+            body.extend(quote! {
+                items.push(#croot::cbor::Cbor::bytes_into_cbor(#field_name)?);
+            });
+        } else {
+            body.extend(quote! {
+                items.push(#field_name.into_cbor()?);
+            });
+        }
+    }
+    (params, body)
+}
+
+fn map_to_named_fields(fields: &FieldsNamed, croot: &TokenStream, name_lit: &str) -> TokenStream {
+    let mut tokens = TokenStream::new();
+    for field in fields.named.iter() {
+        let attrs = parse_field_attrs(&field.attrs);
+        let field_name = field.ident.as_ref().unwrap();
+
+        if attrs.skip {
+            tokens.extend(quote! { #field_name: ::std::default::Default::default(), });
+            continue;
+        }
+
+        let key_lit = field_key_lit(field, &attrs);
+        let ty = &field.ty;
+        let extract = if is_bytes_ty(&field.ty) {
+            quote! { val.into_bytes()? }
+        } else {
+            quote! { <#ty as #croot::cbor::FromCbor>::from_cbor(val)? }
+        };
+        let not_found = if attrs.default {
+            quote! { ::std::default::Default::default() }
+        } else {
+            quote! {
+                #croot::err_at!(
+                    FailConvert, msg: "missing field {} for {}", #key_lit, #name_lit
+                )?
+            }
+        };
+        tokens.extend(quote! {
+            #field_name: match map.iter().position(
+                |(k, _)| matches!(k, #croot::cbor::Key::Text(s) if s == #key_lit)
+            ) {
+                Some(pos) => {
+                    let (_, val) = map.remove(pos);
+                    #extract
+                }
+                None => #not_found,
+            },
+        });
+    }
+    tokens
+}
+
+fn map_to_unnamed_fields(
+    fields: &FieldsUnnamed,
+    croot: &TokenStream,
+) -> (TokenStream, TokenStream) {
+    let mut params = TokenStream::new();
+    let mut body = TokenStream::new();
+    for (field_name, field) in UNNAMED_FIELDS.iter().zip(fields.unnamed.iter()) {
+        let field_name = Ident::new(field_name, field.span());
+        params.extend(quote! { #field_name, });
+
+        let ty = &field.ty;
+        if is_bytes_ty(&field.ty) {
+            body.extend(quote! { items.remove(0).into_bytes()?, });
+        } else {
+            body.extend(
+                quote! { <#ty as #croot::cbor::FromCbor>::from_cbor(items.remove(0))?, },
+            );
+        }
+    }
+    (params, body)
+}
+
+fn into_cbor_for_struct(input: &DeriveInput, fields: &Fields) -> TokenStream {
+    let name = &input.ident;
+    let generics = no_default_generics(input);
+    let croot = quote! { ::mkit };
+
+    let push_fields = match fields {
+        Fields::Unit => quote! {},
+        Fields::Named(fields) => named_fields_into_map(fields, &croot),
+        Fields::Unnamed(_) => {
+            abort_call_site!("unnamed struct not supported for IntoCbor {}", name)
+        }
+    };
+
+    let where_clause = into_cbor_where_clause(&generics, &croot);
+
+    quote! {
+        impl#generics #croot::cbor::IntoCbor for #name#generics #where_clause {
+            fn into_cbor(self) -> #croot::Result<#croot::cbor::Cbor> {
+                let value = self;
+                let mut map: Vec<(#croot::cbor::Key, #croot::cbor::Cbor)> = Vec::default();
+
+                #push_fields
+
+                Ok(map.into_cbor()?)
+            }
+        }
+    }
+}
+
+fn from_cbor_for_struct(input: &DeriveInput, fields: &Fields) -> TokenStream {
+    let name = &input.ident;
+    let name_lit = name.to_string();
+    let generics = no_default_generics(input);
+    let croot = quote! { ::mkit };
+
+    let body = match fields {
+        Fields::Unit => quote! {
+            let _map = Vec::<(#croot::cbor::Key, #croot::cbor::Cbor)>::from_cbor(value)?;
+            Ok(#name)
+        },
+        Fields::Named(fields) => {
+            let token_fields = map_to_named_fields(fields, &croot, &name_lit);
+            quote! {
+                let mut map = Vec::<(#croot::cbor::Key, #croot::cbor::Cbor)>::from_cbor(value)?;
+                Ok(#name { #token_fields })
+            }
+        }
+        Fields::Unnamed(_) => {
+            abort_call_site!("unnamed struct not supported for FromCbor {}", name)
+        }
+    };
+
+    let where_clause = from_cbor_where_clause(&generics, &croot);
+
+    quote! {
+        impl#generics #croot::cbor::FromCbor for #name#generics #where_clause {
+            fn from_cbor(value: #croot::cbor::Cbor) -> #croot::Result<#name#generics> {
+                use #croot::Error;
+
+                #body
+            }
+        }
+    }
+}
+
+fn into_cbor_for_enum(input: &DeriveInput, variants: &[&Variant]) -> TokenStream {
+    let name = &input.ident;
+    let generics = no_default_generics(input);
+    let croot = quote! { ::mkit };
+
+    let mut tok_variants = TokenStream::new();
+    for variant in variants.iter() {
+        let variant_name = &variant.ident;
+        let variant_lit = variant.ident.to_string();
+        let arm = match &variant.fields {
+            Fields::Unit => quote! {
+                #name::#variant_name => (
+                    #croot::cbor::Key::Text(#variant_lit.to_string()),
+                    #croot::cbor::SimpleValue::Null.into_cbor()?,
+                ),
+            },
+            Fields::Named(fields) => {
+                let (params, push_fields) = named_var_fields_into_map(fields, &croot);
+                quote! {
+                    #name::#variant_name{#params} => {
+                        let mut map: Vec<(#croot::cbor::Key, #croot::cbor::Cbor)> = Vec::default();
+                        #push_fields
+                        (#croot::cbor::Key::Text(#variant_lit.to_string()), map.into_cbor()?)
+                    },
+                }
+            }
+            Fields::Unnamed(fields) => {
+                let (params, push_items) = unnamed_fields_into_items(fields, &croot);
+                quote! {
+                    #name::#variant_name(#params) => {
+                        let mut items: Vec<#croot::cbor::Cbor> = Vec::default();
+                        #push_items
+                        (#croot::cbor::Key::Text(#variant_lit.to_string()), items.into_cbor()?)
+                    },
+                }
+            }
+        };
+        tok_variants.extend(arm);
+    }
+
+    let where_clause = into_cbor_where_clause(&generics, &croot);
+
+    quote! {
+        impl#generics #croot::cbor::IntoCbor for #name#generics #where_clause {
+            fn into_cbor(self) -> #croot::Result<#croot::cbor::Cbor> {
+                let value = self;
+                let entry: (#croot::cbor::Key, #croot::cbor::Cbor) = match value {
+                    #tok_variants
+                };
+                Ok(vec![entry].into_cbor()?)
+            }
+        }
+    }
+}
+
+fn from_cbor_for_enum(input: &DeriveInput, variants: &[&Variant]) -> TokenStream {
+    let name = &input.ident;
+    let name_lit = name.to_string();
+    let generics = no_default_generics(input);
+    let croot = quote! { ::mkit };
+
+    let preamble = quote! {
+        let mut map = Vec::<(#croot::cbor::Key, #croot::cbor::Cbor)>::from_cbor(value)?;
+        if map.len() != 1 {
+            #croot::err_at!(
+                FailConvert, msg: "expected single variant entry for {}, got {}",
+                #name_lit, map.len()
+            )?;
+        }
+        let (variant_key, payload) = map.remove(0);
+        let variant_name = match variant_key {
+            #croot::cbor::Key::Text(s) => s,
+            _ => #croot::err_at!(
+                FailConvert, msg: "variant key for {} is not text", #name_lit
+            )?,
+        };
+    };
+
+    let mut tok_variants = TokenStream::new();
+    for variant in variants.iter() {
+        let variant_name = &variant.ident;
+        let variant_lit = variant.ident.to_string();
+        let arm = match &variant.fields {
+            Fields::Unit => quote! {
+                #variant_lit => #name::#variant_name,
+            },
+            Fields::Named(fields) => {
+                let token_fields = map_to_named_fields(fields, &croot, &name_lit);
+                quote! {
+                    #variant_lit => {
+                        let mut map = Vec::<(#croot::cbor::Key, #croot::cbor::Cbor)>::from_cbor(payload)?;
+                        #name::#variant_name { #token_fields }
+                    },
+                }
+            }
+            Fields::Unnamed(fields) => {
+                let n_fields = fields.unnamed.len();
+                let (_, body) = map_to_unnamed_fields(fields, &croot);
+                quote! {
+                    #variant_lit => {
+                        let mut items = Vec::<#croot::cbor::Cbor>::from_cbor(payload)?;
+                        if #n_fields != items.len() {
+                            #croot::err_at!(
+                                FailConvert, msg: "bad arity {} {}", #n_fields, items.len()
+                            )?;
+                        }
+                        #name::#variant_name(#body)
+                    },
+                }
+            }
+        };
+        tok_variants.extend(arm);
+    }
+
+    let where_clause = from_cbor_where_clause(&generics, &croot);
+
+    quote! {
+        impl#generics #croot::cbor::FromCbor for #name#generics #where_clause {
+            fn from_cbor(value: #croot::cbor::Cbor) -> #croot::Result<#name#generics> {
+                use #croot::Error;
+
+                #preamble
+
+                let val = match variant_name.as_str() {
+                    #tok_variants
+                    _ => #croot::err_at!(
+                        FailConvert, msg: "invalid variant_name {} for {}", variant_name, #name_lit
+                    )?,
+                };
+                Ok(val)
+            }
+        }
+    }
+}
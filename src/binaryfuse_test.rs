@@ -0,0 +1,78 @@
+use rand::{prelude::random, rngs::SmallRng, Rng, SeedableRng};
+
+use super::*;
+
+#[test]
+fn test_binary_fuse8() {
+    let seed: u128 = random();
+    println!("test_binary_fuse8 seed {}", seed);
+    let mut rng = SmallRng::from_seed(seed.to_le_bytes());
+
+    let keys: Vec<u64> = (0..100_000).map(|_| rng.gen::<u64>()).collect();
+
+    let mut filter = BinaryFuse8::<BuildHasherDefault>::new();
+    filter.populate(&keys);
+    filter.build().unwrap();
+
+    for key in keys.iter() {
+        assert!(filter.contains(key), "key {} not present", key);
+    }
+
+    let filter = {
+        let bytes = <BinaryFuse8<BuildHasherDefault> as Bloom>::to_bytes(&filter).unwrap();
+        <BinaryFuse8<BuildHasherDefault> as Bloom>::from_bytes(&bytes).unwrap().0
+    };
+
+    for key in keys.iter() {
+        assert!(filter.contains(key), "key {} not present", key);
+    }
+}
+
+#[test]
+fn test_binary_fuse8_from_buf_incremental() {
+    let mut filter = BinaryFuse8::<BuildHasherDefault>::new();
+    filter.populate(&[1_u64, 2, 3]);
+    filter.build().unwrap();
+
+    let bytes = <BinaryFuse8<BuildHasherDefault> as Bloom>::to_bytes(&filter).unwrap();
+
+    let mut partial = &bytes[..bytes.len() - 1];
+    assert!(
+        <BinaryFuse8<BuildHasherDefault> as Bloom>::from_buf(&mut partial)
+            .unwrap()
+            .is_none()
+    );
+
+    let mut full = bytes.as_slice();
+    let (filter, n) = <BinaryFuse8<BuildHasherDefault> as Bloom>::from_buf(&mut full)
+        .unwrap()
+        .unwrap();
+    assert_eq!(n, bytes.len());
+    for key in [1_u64, 2, 3].iter() {
+        assert!(filter.contains(key));
+    }
+}
+
+#[test]
+fn test_binary_fuse8_or() {
+    let seed: u128 = random();
+    println!("test_binary_fuse8_or seed {}", seed);
+    let mut rng = SmallRng::from_seed(seed.to_le_bytes());
+
+    let keys1: Vec<u64> = (0..10_000).map(|_| rng.gen::<u64>()).collect();
+    let keys2: Vec<u64> = (0..10_000).map(|_| rng.gen::<u64>()).collect();
+
+    let mut filter1 = BinaryFuse8::<BuildHasherDefault>::new();
+    filter1.populate(&keys1);
+    filter1.build().unwrap();
+
+    let mut filter2 = BinaryFuse8::<BuildHasherDefault>::new();
+    filter2.populate(&keys2);
+    filter2.build().unwrap();
+
+    let filter = <BinaryFuse8<BuildHasherDefault> as Bloom>::or(&filter1, &filter2).unwrap();
+
+    for key in keys1.iter().chain(keys2.iter()) {
+        assert!(filter.contains(key), "key {} not present", key);
+    }
+}
@@ -0,0 +1,191 @@
+//! Module implement a partitioned, bit-array [Bloom][crate::db::Bloom] filter.
+//!
+//! Unlike [Xor8][crate::xorfilter::Xor8] or [BinaryFuse8][crate::binaryfuse::BinaryFuse8],
+//! which must see every key before they can be built, [BloomFilter] sets its
+//! bits as keys arrive and only uses `build()` to seal itself against further
+//! inserts. The bit array is split into `k` equal partitions, one per hash
+//! function, so that the `k` probes derived for a key (via double hashing)
+//! each land in their own partition and never collide, keeping the effective
+//! false-positive rate close to the classic, non-partitioned formula.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    result,
+};
+
+use crate::{
+    cbor::{Cbor, FromCbor, IntoCbor},
+    db::Bloom,
+    Error, LocalCborize, Result,
+};
+
+// Assumed element count and target false-positive rate when a [BloomFilter]
+// is constructed via [Default], e.g. as a type-parameter default.
+const DEFAULT_ITEMS: usize = 10_000;
+const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+// Fixed mixing constants for the two hash probes, carried over the wire so
+// that a decoded filter hashes keys identically to the one that encoded it.
+// `SEED1` is the golden-ratio constant already used for mixing in
+// `binaryfuse.rs`; `SEED2` is xxHash64's second prime, chosen only because
+// it is a different, well-known odd 64-bit value.
+const SEED1: u64 = 0x9E37_79B9_7F4A_7C15;
+const SEED2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+
+/// A partitioned Bloom filter over a bit array, sized from an expected
+/// element count and a target false-positive rate.
+///
+/// Construct with [BloomFilter::new], feed it keys via `add_key`/
+/// `add_digest32`, and call [Bloom::build] to seal it; further inserts
+/// after `build()` are silently ignored. Two sealed filters built with
+/// the same `m`, `k` and seeds can be merged with [Bloom::or].
+#[derive(Clone, Debug, LocalCborize)]
+pub struct BloomFilter {
+    m: u64,
+    k: u32,
+    seed1: u64,
+    seed2: u64,
+    bits: Vec<u8>,
+    built: bool,
+}
+
+impl BloomFilter {
+    pub const ID: &'static str = "bloomfilter/0.1.0";
+
+    /// Size a new, empty filter for `expected_items` keys at `false_positive_rate`.
+    ///
+    /// `m = -n·ln(p)/ln(2)²` bits, split into `k = round(m/n·ln2)` equal
+    /// partitions, one per hash probe.
+    pub fn new(expected_items: usize, false_positive_rate: f64) -> Self {
+        let n = (expected_items.max(1)) as f64;
+        let p = false_positive_rate.clamp(1e-6, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+
+        let total_bits = (-(n * p.ln()) / (ln2 * ln2)).ceil().max(8.0);
+        let k = ((total_bits / n) * ln2).round().max(1.0) as u32;
+        let partition_bits = (total_bits / f64::from(k)).ceil().max(1.0) as u64;
+        let m = partition_bits * u64::from(k);
+
+        BloomFilter {
+            m,
+            k,
+            seed1: SEED1,
+            seed2: SEED2,
+            bits: vec![0u8; ((m + 7) / 8) as usize],
+            built: false,
+        }
+    }
+
+    fn hash_pair<Q: ?Sized + Hash>(&self, key: &Q) -> (u32, u32) {
+        let mut h1 = DefaultHasher::new();
+        self.seed1.hash(&mut h1);
+        key.hash(&mut h1);
+
+        let mut h2 = DefaultHasher::new();
+        self.seed2.hash(&mut h2);
+        key.hash(&mut h2);
+
+        (h1.finish() as u32, h2.finish() as u32)
+    }
+
+    fn probe_bits(&self, h1: u32, h2: u32) -> impl Iterator<Item = u64> {
+        let partition_bits = self.m / u64::from(self.k);
+        let (h1, h2) = (u64::from(h1), u64::from(h2));
+        (0..u64::from(self.k))
+            .map(move |i| i * partition_bits + (h1.wrapping_add(i.wrapping_mul(h2)) % partition_bits))
+    }
+
+    fn set_bit(&mut self, bit: u64) {
+        let byte = (bit / 8) as usize;
+        self.bits[byte] |= 1u8 << (bit % 8);
+    }
+
+    fn get_bit(&self, bit: u64) -> bool {
+        let byte = (bit / 8) as usize;
+        self.bits[byte] & (1u8 << (bit % 8)) != 0
+    }
+}
+
+impl Default for BloomFilter {
+    fn default() -> Self {
+        BloomFilter::new(DEFAULT_ITEMS, DEFAULT_FALSE_POSITIVE_RATE)
+    }
+}
+
+impl Bloom for BloomFilter {
+    type Err = Error;
+
+    fn add_key<Q: ?Sized + Hash>(&mut self, key: &Q) {
+        if self.built {
+            return;
+        }
+        let (h1, h2) = self.hash_pair(key);
+        for bit in self.probe_bits(h1, h2).collect::<Vec<u64>>() {
+            self.set_bit(bit);
+        }
+    }
+
+    fn add_digest32(&mut self, digest: u32) {
+        self.add_key(&digest);
+    }
+
+    fn build(&mut self) -> Result<()> {
+        self.built = true;
+        Ok(())
+    }
+
+    fn contains<Q: ?Sized + Hash>(&self, element: &Q) -> bool {
+        let (h1, h2) = self.hash_pair(element);
+        self.probe_bits(h1, h2).all(|bit| self.get_bit(bit))
+    }
+
+    fn to_bytes(&self) -> result::Result<Vec<u8>, Self::Err> {
+        let cbor_val = err_at!(IOError, self.clone().into_cbor())?;
+
+        let mut buf: Vec<u8> = vec![];
+        err_at!(IOError, cbor_val.encode(&mut buf))?;
+        Ok(buf)
+    }
+
+    fn from_bytes(mut buf: &[u8]) -> result::Result<(Self, usize), Self::Err> {
+        let (val, n) = err_at!(IOError, Cbor::decode(&mut buf))?;
+        Ok((err_at!(IOError, BloomFilter::from_cbor(val))?, n))
+    }
+
+    fn from_buf<B>(buf: &mut B) -> result::Result<Option<(Self, usize)>, Self::Err>
+    where
+        B: crate::cbor::Buf,
+    {
+        match err_at!(IOError, Cbor::decode_buf(buf))? {
+            None => Ok(None),
+            Some((val, n)) => {
+                let filter = err_at!(IOError, BloomFilter::from_cbor(val))?;
+                Ok(Some((filter, n)))
+            }
+        }
+    }
+
+    fn or(&self, other: &Self) -> result::Result<Self, Self::Err> {
+        if self.m != other.m || self.k != other.k || self.seed1 != other.seed1 || self.seed2 != other.seed2 {
+            err_at!(
+                Fatal,
+                msg: "cannot merge bloom filters with different m/k/seeds"
+            )?
+        } else {
+            let bits = self.bits.iter().zip(other.bits.iter()).map(|(a, b)| a | b).collect();
+            Ok(BloomFilter {
+                m: self.m,
+                k: self.k,
+                seed1: self.seed1,
+                seed2: self.seed2,
+                bits,
+                built: true,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+#[path = "bloomfilter_test.rs"]
+mod bloomfilter_test;
@@ -0,0 +1,111 @@
+use rand::{prelude::random, rngs::SmallRng, Rng, SeedableRng};
+
+use super::*;
+
+#[test]
+fn test_basic() {
+    let seed: u128 = random();
+    println!("test_basic seed {}", seed);
+    let mut rng = SmallRng::from_seed(seed.to_le_bytes());
+
+    let keys: Vec<u64> = (0..10_000).map(|_| rng.gen::<u64>()).collect();
+
+    let mut filter = BloomFilter::new(keys.len(), 0.01);
+    for key in keys.iter() {
+        filter.add_key(key);
+    }
+    filter.build().unwrap();
+
+    for key in keys.iter() {
+        assert!(filter.contains(key), "key {} not present", key);
+    }
+}
+
+#[test]
+fn test_build_rejects_further_inserts() {
+    let mut filter = BloomFilter::new(10, 0.01);
+    filter.add_key(&1_u64);
+    filter.build().unwrap();
+
+    filter.add_key(&2_u64);
+    assert!(filter.contains(&1_u64));
+    assert!(!filter.contains(&2_u64));
+}
+
+#[test]
+fn test_roundtrip() {
+    let mut filter = BloomFilter::new(100, 0.01);
+    for key in 0_u64..100 {
+        filter.add_key(&key);
+    }
+    filter.build().unwrap();
+
+    let bytes = <BloomFilter as Bloom>::to_bytes(&filter).unwrap();
+    let (filter, _) = <BloomFilter as Bloom>::from_bytes(&bytes).unwrap();
+
+    for key in 0_u64..100 {
+        assert!(filter.contains(&key));
+    }
+}
+
+#[test]
+fn test_or() {
+    let mut filter1 = BloomFilter::new(1_000, 0.01);
+    let keys1: Vec<u64> = (0_u64..500).collect();
+    for key in keys1.iter() {
+        filter1.add_key(key);
+    }
+    filter1.build().unwrap();
+
+    let mut filter2 = BloomFilter::new(1_000, 0.01);
+    let keys2: Vec<u64> = (500_u64..1_000).collect();
+    for key in keys2.iter() {
+        filter2.add_key(key);
+    }
+    filter2.build().unwrap();
+
+    let filter = <BloomFilter as Bloom>::or(&filter1, &filter2).unwrap();
+    for key in keys1.iter().chain(keys2.iter()) {
+        assert!(filter.contains(key), "key {} not present", key);
+    }
+}
+
+#[test]
+fn test_or_mismatched_params_errors() {
+    let filter1 = BloomFilter::new(10, 0.01);
+    let filter2 = BloomFilter::new(10_000, 0.01);
+    assert!(<BloomFilter as Bloom>::or(&filter1, &filter2).is_err());
+}
+
+#[test]
+fn test_from_buf_incremental() {
+    let mut filter = BloomFilter::new(3, 0.01);
+    for key in [1_u64, 2, 3].iter() {
+        filter.add_key(key);
+    }
+    filter.build().unwrap();
+
+    let bytes = <BloomFilter as Bloom>::to_bytes(&filter).unwrap();
+
+    // a truncated buffer must report "need more data" instead of erroring.
+    let mut partial = &bytes[..bytes.len() - 1];
+    assert!(<BloomFilter as Bloom>::from_buf(&mut partial)
+        .unwrap()
+        .is_none());
+
+    let mut full = bytes.as_slice();
+    let (filter, n) = <BloomFilter as Bloom>::from_buf(&mut full).unwrap().unwrap();
+    assert_eq!(n, bytes.len());
+    for key in [1_u64, 2, 3].iter() {
+        assert!(filter.contains(key));
+    }
+}
+
+#[test]
+fn test_add_digest32() {
+    let mut filter = BloomFilter::new(10, 0.01);
+    filter.add_digest32(42);
+    filter.build().unwrap();
+
+    assert!(filter.contains(&42_u32));
+}
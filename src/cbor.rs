@@ -9,6 +9,7 @@
 
 use arbitrary::{self, Arbitrary, Unstructured};
 
+use crate::data::{Conflict, Diff, Merge3};
 use crate::{Error, Result};
 
 #[cfg(unix)]
@@ -16,9 +17,12 @@ use std::os::unix::ffi::OsStringExt;
 #[cfg(windows)]
 use std::os::windows::ffi::OsStringExt;
 use std::{
+    borrow::Cow,
     cmp,
+    collections::{BTreeMap, HashMap},
     convert::{TryFrom, TryInto},
-    ffi, io,
+    ffi, hash, io, result,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 macro_rules! read_r {
@@ -54,6 +58,49 @@ pub trait FromCbor: Sized {
 /// Recursion limit for nested Cbor objects.
 pub const RECURSION_LIMIT: u32 = 1000;
 
+/// Resource limits enforced by [Cbor::decode_with_config], for decoding
+/// bytes arriving off an untrusted, possibly adversarial transport.
+/// Unlike plain [Cbor::decode] -- which trusts the wire's own length
+/// prefixes and bounds nesting only by [RECURSION_LIMIT] -- this rejects a
+/// value whose declared nesting or byte/element length exceeds these
+/// limits before recursing further or allocating, instead of risking a
+/// blown stack or an enormous up-front allocation.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeConfig {
+    /// Max recursion depth across nested arrays/maps/tags.
+    pub max_nested_depth: u32,
+    /// Max accepted length, in bytes or elements, for a single
+    /// byte-string/text-string/array/map.
+    pub max_length: usize,
+}
+
+impl Default for DecodeConfig {
+    fn default() -> DecodeConfig {
+        DecodeConfig {
+            max_nested_depth: 256,
+            max_length: 128 * 1024 * 1024,
+        }
+    }
+}
+
+// The limits `Cbor::decode` itself applies -- unbounded length, and the
+// same `RECURSION_LIMIT` depth it has always enforced -- so that adding
+// `DecodeConfig` doesn't change `decode`'s existing behavior for callers
+// who haven't opted into [Cbor::decode_with_config]'s tighter defaults.
+const UNBOUNDED_DECODE_CONFIG: DecodeConfig = DecodeConfig {
+    max_nested_depth: RECURSION_LIMIT,
+    max_length: usize::MAX,
+};
+
+/// RFC 8949 §3.4.6 "Self-describe CBOR" tag-number.
+///
+/// Wrapping an encoded value with this tag lets a reader positively
+/// recognize a byte-stream as CBOR, and a decoder dispatch on the tag
+/// up front instead of first learning the concrete Rust type that
+/// produced the bytes. See [Cbor::encode_self_described] and
+/// [Cbor::decode_self_described].
+pub const SELF_DESCRIBE_TAG: u64 = 55799;
+
 /// Cbor type enumerated over its major variants.
 ///
 /// Use one of the conversion trait to convert language-native-type to a
@@ -69,6 +116,7 @@ pub enum Cbor {
     Major6(Info, Tag),              // tags similar to major0
     Major7(Info, SimpleValue),      // type refer SimpleValue
     Binary(Vec<u8>),                // for lazy decoding cbor data
+    Tag(u64, Box<Cbor>),            // tag-number followed by a tagged item
 }
 
 impl arbitrary::Arbitrary for Cbor {
@@ -115,7 +163,9 @@ impl arbitrary::Arbitrary for Cbor {
                 Major5(info, val)
             }
             6 => {
-                let tag: Tag = u.arbitrary()?;
+                // Qualified: the glob import above also brings in the
+                // `Cbor::Tag` variant, which shadows the `Tag` type name.
+                let tag: self::Tag = u.arbitrary()?;
                 tag.into()
             }
             7 => {
@@ -156,6 +206,13 @@ impl Cbor {
                 let n = encode_hdr(major, *info, w)?;
                 n + encode_addnl(*num, w)?
             }
+            Cbor::Major2(info, byts) if matches!(info, Info::Indefinite) => {
+                let n = encode_hdr(major, *info, w)?;
+                let chunk_info = err_at!(FailConvert, u64::try_from(byts.len()))?.into();
+                let m = Cbor::Major2(chunk_info, byts.clone()).do_encode(w, depth + 1)?;
+                let k = Cbor::Major7(Info::Indefinite, SimpleValue::Break).do_encode(w, depth + 1)?;
+                n + m + k
+            }
             Cbor::Major2(info, byts) => {
                 let n = encode_hdr(major, *info, w)?;
                 let m =
@@ -163,12 +220,28 @@ impl Cbor {
                 write_w!(w, &byts);
                 n + m + byts.len()
             }
+            Cbor::Major3(info, text) if matches!(info, Info::Indefinite) => {
+                let n = encode_hdr(major, *info, w)?;
+                let chunk_info = err_at!(FailConvert, u64::try_from(text.len()))?.into();
+                let m = Cbor::Major3(chunk_info, text.clone()).do_encode(w, depth + 1)?;
+                let k = Cbor::Major7(Info::Indefinite, SimpleValue::Break).do_encode(w, depth + 1)?;
+                n + m + k
+            }
             Cbor::Major3(info, text) => {
                 let n = encode_hdr(major, *info, w)?;
                 let m = encode_addnl(err_at!(FailCbor, u64::try_from(text.len()))?, w)?;
                 write_w!(w, &text);
                 n + m + text.len()
             }
+            Cbor::Major4(info, list) if matches!(info, Info::Indefinite) => {
+                let n = encode_hdr(major, *info, w)?;
+                let mut acc = 0;
+                for x in list.iter() {
+                    acc += x.do_encode(w, depth + 1)?;
+                }
+                let k = Cbor::Major7(Info::Indefinite, SimpleValue::Break).do_encode(w, depth + 1)?;
+                n + acc + k
+            }
             Cbor::Major4(info, list) => {
                 let n = encode_hdr(major, *info, w)?;
                 let m =
@@ -179,6 +252,17 @@ impl Cbor {
                 }
                 n + m + acc
             }
+            Cbor::Major5(info, map) if matches!(info, Info::Indefinite) => {
+                let n = encode_hdr(major, *info, w)?;
+                let mut acc = 0;
+                for (key, val) in map.iter() {
+                    let key = key.clone().into_cbor()?;
+                    acc += key.do_encode(w, depth + 1)?;
+                    acc += val.do_encode(w, depth + 1)?;
+                }
+                let k = Cbor::Major7(Info::Indefinite, SimpleValue::Break).do_encode(w, depth + 1)?;
+                n + acc + k
+            }
             Cbor::Major5(info, map) => {
                 let n = encode_hdr(major, *info, w)?;
                 let m = encode_addnl(err_at!(FailConvert, u64::try_from(map.len()))?, w)?;
@@ -204,6 +288,12 @@ impl Cbor {
                 write_w!(w, data);
                 data.len()
             }
+            Cbor::Tag(num, val) => {
+                let n = encode_hdr(major, (*num).into(), w)?;
+                let m = encode_addnl(*num, w)?;
+                let k = val.do_encode(w, depth + 1)?;
+                n + m + k
+            }
         };
 
         Ok(n)
@@ -215,15 +305,26 @@ impl Cbor {
     where
         R: io::Read,
     {
-        Cbor::do_decode(r, 1)
+        Cbor::do_decode(r, 1, &UNBOUNDED_DECODE_CONFIG)
     }
 
-    fn do_decode<R>(r: &mut R, depth: u32) -> Result<(Cbor, usize)>
+    /// Like [Cbor::decode], but guards against adversarial input by
+    /// rejecting nesting and declared byte-string/array/map lengths
+    /// beyond what `config` allows, before recursing further or
+    /// allocating -- see [DecodeConfig].
+    pub fn decode_with_config<R>(r: &mut R, config: &DecodeConfig) -> Result<(Cbor, usize)>
     where
         R: io::Read,
     {
-        if depth > RECURSION_LIMIT {
-            return err_at!(FailCbor, msg: "decode recursion limt exceeded");
+        Cbor::do_decode(r, 1, config)
+    }
+
+    fn do_decode<R>(r: &mut R, depth: u32, cfg: &DecodeConfig) -> Result<(Cbor, usize)>
+    where
+        R: io::Read,
+    {
+        if depth > cfg.max_nested_depth {
+            err_at!(FailCbor, msg: "decode nesting depth exceeds max_nested_depth {}", cfg.max_nested_depth)?;
         }
 
         let (major, info, n) = decode_hdr(r)?;
@@ -241,19 +342,20 @@ impl Cbor {
                 let mut data: Vec<u8> = Vec::default();
                 let mut m = 0_usize;
                 loop {
-                    let (val, k) = Cbor::do_decode(r, depth + 1)?;
+                    let (val, k) = Cbor::do_decode(r, depth + 1, cfg)?;
+                    m += k;
                     match val {
                         Cbor::Major2(_, chunk) => data.extend_from_slice(&chunk),
                         Cbor::Major7(_, SimpleValue::Break) => break,
                         _ => err_at!(FailConvert, msg: "expected byte chunk")?,
                     }
-                    m += k;
                 }
                 (Cbor::Major2(info, data), m)
             }
             (2, info) => {
                 let (val, m) = decode_addnl(info, r)?;
                 let len: usize = err_at!(FailConvert, val.try_into())?;
+                Self::check_max_length(len, cfg)?;
                 let mut data = vec![0; len];
                 read_r!(r, &mut data);
                 (Cbor::Major2(info, data), m + len)
@@ -262,19 +364,20 @@ impl Cbor {
                 let mut text: Vec<u8> = Vec::default();
                 let mut m = 0_usize;
                 loop {
-                    let (val, k) = Cbor::do_decode(r, depth + 1)?;
+                    let (val, k) = Cbor::do_decode(r, depth + 1, cfg)?;
+                    m += k;
                     match val {
                         Cbor::Major3(_, chunk) => text.extend_from_slice(&chunk),
                         Cbor::Major7(_, SimpleValue::Break) => break,
                         _ => err_at!(FailConvert, msg: "expected byte chunk")?,
                     }
-                    m += k;
                 }
                 (Cbor::Major3(info, text), m)
             }
             (3, info) => {
                 let (val, m) = decode_addnl(info, r)?;
                 let len: usize = err_at!(FailConvert, val.try_into())?;
+                Self::check_max_length(len, cfg)?;
                 let mut text = vec![0; len];
                 read_r!(r, &mut text);
                 (Cbor::Major3(info, text), m + len)
@@ -283,53 +386,59 @@ impl Cbor {
                 let mut list: Vec<Cbor> = vec![];
                 let mut m = 0_usize;
                 loop {
-                    let (val, k) = Cbor::do_decode(r, depth + 1)?;
+                    let (val, k) = Cbor::do_decode(r, depth + 1, cfg)?;
+                    m += k;
                     match val {
                         Cbor::Major7(_, SimpleValue::Break) => break,
                         item => list.push(item),
                     }
-                    m += k;
                 }
                 (Cbor::Major4(info, list), m)
             }
             (4, info) => {
                 let mut list: Vec<Cbor> = vec![];
                 let (len, mut m) = decode_addnl(info, r)?;
+                Self::check_max_length(err_at!(FailConvert, usize::try_from(len))?, cfg)?;
                 for _ in 0..len {
-                    let (val, k) = Cbor::do_decode(r, depth + 1)?;
+                    let (val, k) = Cbor::do_decode(r, depth + 1, cfg)?;
                     list.push(val);
                     m += k;
                 }
                 (Cbor::Major4(info, list), m)
             }
             (5, Info::Indefinite) => {
+                // Per RFC 8949 §3.2.2, the break stop-code can only ever
+                // appear where the next key would be, so it must be
+                // checked for right after decoding `key`, before even
+                // attempting to decode a paired value.
                 let mut map: Vec<(Key, Cbor)> = Vec::default();
                 let mut m = 0_usize;
                 loop {
-                    let (key, j) = Cbor::do_decode(r, depth + 1)?;
-                    let (val, k) = Cbor::do_decode(r, depth + 1)?;
-                    let val = match val {
-                        Cbor::Major7(_, SimpleValue::Break) => break,
-                        val => val,
-                    };
+                    let (key, j) = Cbor::do_decode(r, depth + 1, cfg)?;
+                    m += j;
+                    if matches!(key, Cbor::Major7(_, SimpleValue::Break)) {
+                        break;
+                    }
+                    let (val, k) = Cbor::do_decode(r, depth + 1, cfg)?;
+                    m += k;
                     map.push((Key::from_cbor(key)?, val));
-                    m += j + k;
                 }
                 (Cbor::Major5(info, map), m)
             }
             (5, info) => {
                 let mut map: Vec<(Key, Cbor)> = Vec::default();
                 let (len, mut m) = decode_addnl(info, r)?;
+                Self::check_max_length(err_at!(FailConvert, usize::try_from(len))?, cfg)?;
                 for _ in 0..len {
-                    let (key, j) = Cbor::do_decode(r, depth + 1)?;
-                    let (val, k) = Cbor::do_decode(r, depth + 1)?;
+                    let (key, j) = Cbor::do_decode(r, depth + 1, cfg)?;
+                    let (val, k) = Cbor::do_decode(r, depth + 1, cfg)?;
                     map.push((Key::from_cbor(key)?, val));
                     m += j + k;
                 }
                 (Cbor::Major5(info, map), m)
             }
             (6, info) => {
-                let (tag, m) = Tag::decode(info, r)?;
+                let (tag, m) = Tag::decode(info, r, depth, cfg)?;
                 (Cbor::Major6(info, tag), m)
             }
             (7, info) => {
@@ -342,6 +451,16 @@ impl Cbor {
         Ok((val, (m + n)))
     }
 
+    // Reject a byte-string/text-string/array/map whose declared length
+    // -- read from the wire, before a single byte of payload -- exceeds
+    // `cfg.max_length`, instead of trusting it enough to allocate for.
+    fn check_max_length(len: usize, cfg: &DecodeConfig) -> Result<()> {
+        if len > cfg.max_length {
+            err_at!(FailCbor, msg: "declared length {} exceeds max_length {}", len, cfg.max_length)?;
+        }
+        Ok(())
+    }
+
     fn to_major_val(&self) -> u8 {
         match self {
             Cbor::Major0(_, _) => 0,
@@ -353,6 +472,7 @@ impl Cbor {
             Cbor::Major6(_, _) => 6,
             Cbor::Major7(_, _) => 7,
             Cbor::Binary(data) => (data[0] & 0xe0) >> 5,
+            Cbor::Tag(_, _) => 6,
         }
     }
 
@@ -375,6 +495,864 @@ impl Cbor {
             _ => err_at!(FailConvert, msg: "not bytes"),
         }
     }
+
+    /// Wrap `self` in the [SELF_DESCRIBE_TAG] tag and serialize it, so
+    /// that a peer can recognize the stream as self-describing CBOR and
+    /// reject mismatched payloads before decoding the tagged value.
+    pub fn encode_self_described<W>(&self, w: &mut W) -> Result<usize>
+    where
+        W: io::Write,
+    {
+        Cbor::Tag(SELF_DESCRIBE_TAG, Box::new(self.clone())).encode(w)
+    }
+
+    /// Converse of [Cbor::encode_self_described]. Fails if the leading
+    /// tag is missing or doesn't match [SELF_DESCRIBE_TAG].
+    pub fn decode_self_described<R>(r: &mut R) -> Result<(Cbor, usize)>
+    where
+        R: io::Read,
+    {
+        let (major, info, n) = decode_hdr(r)?;
+        if major != 6 {
+            err_at!(FailCbor, msg: "missing self-describe-cbor tag")?;
+        }
+        let (tag, m) = decode_addnl(info, r)?;
+        if tag != SELF_DESCRIBE_TAG {
+            err_at!(FailCbor, msg: "expected self-describe-cbor tag, found {}", tag)?;
+        }
+        let (val, k) = Cbor::do_decode(r, 1, &UNBOUNDED_DECODE_CONFIG)?;
+        Ok((val, n + m + k))
+    }
+
+    /// Incremental counterpart of [Cbor::decode], pulling bytes from an
+    /// abstract cursor, `buf`, instead of requiring a contiguous, fully
+    /// buffered `io::Read`-er.
+    ///
+    /// Returns `Ok(None)` when `buf` does not yet hold a complete value,
+    /// leaving `buf` untouched so the caller can append more bytes, say
+    /// from a socket or a chunked file-read, and retry. Returns
+    /// `Ok(Some((value, n)))`, with `n` the number of bytes consumed,
+    /// once a complete value is available.
+    pub fn decode_buf<B>(buf: &mut B) -> Result<Option<(Cbor, usize)>>
+    where
+        B: Buf,
+    {
+        match scan_len(buf.chunk(), 1)? {
+            None => Ok(None),
+            Some(len) => {
+                // `len` is the exact count of physical bytes `scan_len`
+                // walked to find this value's boundary, which is what
+                // `buf` must be advanced by.
+                let mut slice = &buf.chunk()[..len];
+                let (val, _) = Cbor::decode(&mut slice)?;
+                buf.advance(len);
+                Ok(Some((val, len)))
+            }
+        }
+    }
+
+    /// Incremental counterpart of [Cbor::decode_self_described], built
+    /// over a [Buf] cursor the same way [Cbor::decode_buf] complements
+    /// [Cbor::decode]. Returns `Ok(None)` while `buf` doesn't yet hold a
+    /// complete, tagged value.
+    pub fn decode_self_described_buf<B>(buf: &mut B) -> Result<Option<(Cbor, usize)>>
+    where
+        B: Buf,
+    {
+        let (major, info, hdr_len) = match peek_hdr(buf.chunk())? {
+            None => return Ok(None),
+            Some(hdr) => hdr,
+        };
+        if major != 6 {
+            err_at!(FailCbor, msg: "missing self-describe-cbor tag")?;
+        }
+        let tag = addnl_value(info, &buf.chunk()[1..hdr_len]);
+        if tag != SELF_DESCRIBE_TAG {
+            err_at!(FailCbor, msg: "expected self-describe-cbor tag, found {}", tag)?;
+        }
+
+        match scan_len(&buf.chunk()[hdr_len..], 1)? {
+            None => Ok(None),
+            Some(k) => {
+                let len = hdr_len + k;
+                let mut slice = &buf.chunk()[..len];
+                let (val, _) = Cbor::decode_self_described(&mut slice)?;
+                buf.advance(len);
+                Ok(Some((val, len)))
+            }
+        }
+    }
+
+    /// Zero-copy counterpart of [Cbor::decode], for a caller that already
+    /// holds the complete value in one contiguous `&[u8]` -- a network
+    /// frame, a memory-mapped file, a slice pulled off a larger buffer --
+    /// instead of an `io::Read`-er. [CborRef::Major2]/[CborRef::Major3]
+    /// payloads borrow directly from `buf`; every other variant decodes
+    /// the same as [Cbor::decode]. Returns the decoded value and the
+    /// number of bytes of `buf` it consumed, so a caller holding a
+    /// stream of concatenated items can decode the next one by re-slicing
+    /// `buf` at that offset.
+    pub fn decode_slice(buf: &[u8]) -> Result<(CborRef<'_>, usize)> {
+        do_decode_slice(buf, 1)
+    }
+
+    /// Serialize into RFC 8949 §4.2 "Deterministic Encoding" form, so
+    /// that two semantically equal values always produce identical
+    /// bytes -- the property content-addressing and signing need.
+    /// Compared to [Cbor::encode], this additionally: re-emits every
+    /// length/integer header in its shortest `Info` form (even if
+    /// `self` was hand-built with a wider one); re-emits indefinite-length
+    /// byte/text/array/map values as definite-length;
+    /// sorts [Major5] map entries by the lexicographic byte order of
+    /// each key's own canonical encoding; and narrows floats to the
+    /// smallest of `f16`/`f32`/`f64` that round-trips exactly.
+    pub fn encode_canonical<W>(&self, w: &mut W) -> Result<usize>
+    where
+        W: io::Write,
+    {
+        self.to_canonical()?.do_encode(w, 1)
+    }
+
+    fn to_canonical(&self) -> Result<Cbor> {
+        let val = match self {
+            Cbor::Major0(_, num) => Cbor::Major0((*num).into(), *num),
+            Cbor::Major1(_, num) => Cbor::Major1((*num).into(), *num),
+            Cbor::Major2(_, byts) => {
+                let info = err_at!(FailConvert, u64::try_from(byts.len()))?.into();
+                Cbor::Major2(info, byts.clone())
+            }
+            Cbor::Major3(_, text) => {
+                let info = err_at!(FailConvert, u64::try_from(text.len()))?.into();
+                Cbor::Major3(info, text.clone())
+            }
+            Cbor::Major4(_, list) => {
+                let list: Vec<Cbor> =
+                    list.iter().map(Cbor::to_canonical).collect::<Result<_>>()?;
+                let info = err_at!(FailConvert, u64::try_from(list.len()))?.into();
+                Cbor::Major4(info, list)
+            }
+            Cbor::Major5(_, map) => {
+                let mut items = map
+                    .iter()
+                    .map(|(key, val)| -> Result<(Vec<u8>, Key, Cbor)> {
+                        let mut kbuf = vec![];
+                        key.clone().into_cbor()?.do_encode(&mut kbuf, 1)?;
+                        Ok((kbuf, key.clone(), val.to_canonical()?))
+                    })
+                    .collect::<Result<Vec<(Vec<u8>, Key, Cbor)>>>()?;
+                items.sort_by(|a, b| a.0.cmp(&b.0));
+                let map: Vec<(Key, Cbor)> =
+                    items.into_iter().map(|(_, key, val)| (key, val)).collect();
+                let info = err_at!(FailConvert, u64::try_from(map.len()))?.into();
+                Cbor::Major5(info, map)
+            }
+            Cbor::Major6(_, Tag::Value(num)) => Cbor::Major6((*num).into(), Tag::Value(*num)),
+            Cbor::Major6(_, Tag::Identifier(val)) => {
+                let val = Box::new(val.to_canonical()?);
+                Cbor::Major6(39_u64.into(), Tag::Identifier(val))
+            }
+            Cbor::Major6(_, Tag::DateTime(time)) => Cbor::Major6(0_u64.into(), Tag::DateTime(*time)),
+            Cbor::Major6(_, Tag::EpochTime(time)) => {
+                Cbor::Major6(1_u64.into(), Tag::EpochTime(*time))
+            }
+            Cbor::Major6(_, Tag::BigNum(num)) => {
+                let tag = Tag::BigNum(num.clone());
+                let info = tag.to_tag_value().into();
+                Cbor::Major6(info, tag)
+            }
+            Cbor::Major6(_, Tag::ExpectedEncoding(encoding, val)) => {
+                let val = Box::new(val.to_canonical()?);
+                Cbor::Major6(encoding.to_tag_value().into(), Tag::ExpectedEncoding(*encoding, val))
+            }
+            Cbor::Major6(_, Tag::Link(cid)) => Cbor::Major6(42_u64.into(), Tag::Link(cid.clone())),
+            Cbor::Major7(_, SimpleValue::F64(f)) => match SimpleValue::from_f64_smallest(*f) {
+                val @ SimpleValue::F16(_) => Cbor::Major7(Info::U16, val),
+                val @ SimpleValue::F32(_) => Cbor::Major7(Info::U32, val),
+                val @ SimpleValue::F64(_) => Cbor::Major7(Info::U64, val),
+                _ => unreachable!(),
+            },
+            Cbor::Major7(_, SimpleValue::F32(f)) => {
+                // f32 -> f16 has no `from_f64_smallest` helper of its
+                // own; widen back to f64 and reuse it.
+                match SimpleValue::from_f64_smallest(f64::from(*f)) {
+                    val @ SimpleValue::F16(_) => Cbor::Major7(Info::U16, val),
+                    _ => Cbor::Major7(Info::U32, SimpleValue::F32(*f)),
+                }
+            }
+            val @ Cbor::Major7(_, _) => val.clone(),
+            Cbor::Binary(_) => {
+                err_at!(FailCbor, msg: "cannot canonicalize a lazily-encoded Binary value")?
+            }
+            Cbor::Tag(num, val) => Cbor::Tag(*num, Box::new(val.to_canonical()?)),
+        };
+        Ok(val)
+    }
+
+    /// Converse of [Cbor::encode_canonical]: decode `r` the same way as
+    /// [Cbor::decode], then validate that every invariant canonical
+    /// encoding guarantees -- shortest-form headers, definite lengths,
+    /// sorted map keys, smallest-width floats -- actually holds,
+    /// returning `FailCbor` the moment one doesn't. Use this when a peer
+    /// must be able to detect non-canonical input, e.g. before trusting
+    /// it for content-addressing or signature verification.
+    pub fn decode_canonical<R>(r: &mut R) -> Result<(Cbor, usize)>
+    where
+        R: io::Read,
+    {
+        let (val, n) = Cbor::decode(r)?;
+        val.validate_canonical()?;
+        Ok((val, n))
+    }
+
+    fn validate_canonical(&self) -> Result<()> {
+        match self {
+            Cbor::Major0(info, num) | Cbor::Major1(info, num) => {
+                if *info != (*num).into() {
+                    err_at!(FailCbor, msg: "integer not encoded in shortest form")?;
+                }
+            }
+            Cbor::Major2(info, byts) => {
+                Self::validate_canonical_len(*info, byts.len())?;
+            }
+            Cbor::Major3(info, text) => {
+                Self::validate_canonical_len(*info, text.len())?;
+            }
+            Cbor::Major4(info, list) => {
+                Self::validate_canonical_len(*info, list.len())?;
+                for item in list.iter() {
+                    item.validate_canonical()?;
+                }
+            }
+            Cbor::Major5(info, map) => {
+                Self::validate_canonical_len(*info, map.len())?;
+                let mut prev: Option<Vec<u8>> = None;
+                for (key, val) in map.iter() {
+                    let mut kbuf = vec![];
+                    key.clone().into_cbor()?.do_encode(&mut kbuf, 1)?;
+                    if let Some(prev) = prev.as_ref() {
+                        if kbuf < *prev {
+                            err_at!(FailCbor, msg: "map keys not in canonical byte order")?;
+                        }
+                    }
+                    prev = Some(kbuf);
+                    val.validate_canonical()?;
+                }
+            }
+            Cbor::Major6(info, Tag::Value(num)) => {
+                if *info != (*num).into() {
+                    err_at!(FailCbor, msg: "tag not encoded in shortest form")?;
+                }
+            }
+            Cbor::Major6(info, Tag::Identifier(val)) => {
+                if *info != 39_u64.into() {
+                    err_at!(FailCbor, msg: "tag not encoded in shortest form")?;
+                }
+                val.validate_canonical()?;
+            }
+            Cbor::Major6(info, Tag::DateTime(_)) => {
+                if *info != 0_u64.into() {
+                    err_at!(FailCbor, msg: "tag not encoded in shortest form")?;
+                }
+            }
+            Cbor::Major6(info, Tag::EpochTime(_)) => {
+                if *info != 1_u64.into() {
+                    err_at!(FailCbor, msg: "tag not encoded in shortest form")?;
+                }
+            }
+            Cbor::Major6(info, Tag::BigNum(num)) => {
+                let want: Info = if num.negative { 3_u64.into() } else { 2_u64.into() };
+                if *info != want {
+                    err_at!(FailCbor, msg: "tag not encoded in shortest form")?;
+                }
+            }
+            Cbor::Major6(info, Tag::ExpectedEncoding(encoding, val)) => {
+                if *info != encoding.to_tag_value().into() {
+                    err_at!(FailCbor, msg: "tag not encoded in shortest form")?;
+                }
+                val.validate_canonical()?;
+            }
+            Cbor::Major6(info, Tag::Link(_)) => {
+                if *info != 42_u64.into() {
+                    err_at!(FailCbor, msg: "tag not encoded in shortest form")?;
+                }
+            }
+            Cbor::Major7(_, SimpleValue::F64(f)) => {
+                if !matches!(SimpleValue::from_f64_smallest(*f), SimpleValue::F64(_)) {
+                    err_at!(FailCbor, msg: "float not encoded in smallest width")?;
+                }
+            }
+            Cbor::Major7(_, SimpleValue::F32(f)) => {
+                // `NaN == NaN` is always false, so the round-trip
+                // comparison below can never flag a NaN as narrowable;
+                // every NaN always fits in `F16`'s canonical NaN pattern.
+                let narrows = if f.is_nan() {
+                    true
+                } else {
+                    let bits16 = SimpleValue::f32_to_f16(*f);
+                    SimpleValue::f16_to_f32(bits16) == *f
+                };
+                if narrows {
+                    err_at!(FailCbor, msg: "float not encoded in smallest width")?;
+                }
+            }
+            Cbor::Major7(_, _) => (),
+            Cbor::Binary(_) => {
+                err_at!(FailCbor, msg: "lazily-encoded Binary value is not canonical")?
+            }
+            Cbor::Tag(_, val) => val.validate_canonical()?,
+        }
+        Ok(())
+    }
+
+    fn validate_canonical_len(info: Info, len: usize) -> Result<()> {
+        if matches!(info, Info::Indefinite) {
+            err_at!(FailCbor, msg: "indefinite-length value is not canonical")?;
+        }
+        let want: Info = err_at!(FailConvert, u64::try_from(len))?.into();
+        if info != want {
+            err_at!(FailCbor, msg: "length not encoded in shortest form")?;
+        }
+        Ok(())
+    }
+
+    /// Render `self` as [RFC 8949 §8] diagnostic notation, e.g.
+    /// `[1, 2, {"a": h'0102', 3.14, true, null}]`. Byte strings are
+    /// shown as `h'..'`, indefinite-length items are prefixed with `_`,
+    /// tags are rendered as `<num>(<item>)`, and floats use their
+    /// minimal round-tripping decimal form. Useful for golden-file
+    /// tests of the encoder and for diagnosing interop failures.
+    ///
+    /// [RFC 8949 §8]: https://www.rfc-editor.org/rfc/rfc8949.html#section-8
+    pub fn to_diag(&self) -> Result<String> {
+        let mut s = String::new();
+        self.do_to_diag(&mut s, 1)?;
+        Ok(s)
+    }
+
+    fn do_to_diag(&self, s: &mut String, depth: u32) -> Result<()> {
+        if depth > RECURSION_LIMIT {
+            return err_at!(FailCbor, msg: "diagnostic-notation recursion limit exceeded");
+        }
+
+        match self {
+            Cbor::Major0(_, num) => s.push_str(&num.to_string()),
+            Cbor::Major1(_, num) => s.push_str(&(-1 - (*num as i128)).to_string()),
+            Cbor::Major2(info, byts) => {
+                if matches!(info, Info::Indefinite) {
+                    s.push('_');
+                    s.push(' ');
+                }
+                s.push_str("h'");
+                for byt in byts.iter() {
+                    s.push_str(&format!("{:02x}", byt));
+                }
+                s.push('\'');
+            }
+            Cbor::Major3(info, text) => {
+                if matches!(info, Info::Indefinite) {
+                    s.push('_');
+                    s.push(' ');
+                }
+                let text = err_at!(FailConvert, std::str::from_utf8(text))?;
+                s.push_str(&format!("{:?}", text));
+            }
+            Cbor::Major4(info, list) => {
+                if matches!(info, Info::Indefinite) {
+                    s.push('_');
+                    s.push(' ');
+                }
+                s.push('[');
+                for (i, item) in list.iter().enumerate() {
+                    if i > 0 {
+                        s.push_str(", ");
+                    }
+                    item.do_to_diag(s, depth + 1)?;
+                }
+                s.push(']');
+            }
+            Cbor::Major5(info, map) => {
+                if matches!(info, Info::Indefinite) {
+                    s.push('_');
+                    s.push(' ');
+                }
+                s.push('{');
+                for (i, (key, val)) in map.iter().enumerate() {
+                    if i > 0 {
+                        s.push_str(", ");
+                    }
+                    key.clone().into_cbor()?.do_to_diag(s, depth + 1)?;
+                    s.push_str(": ");
+                    val.do_to_diag(s, depth + 1)?;
+                }
+                s.push('}');
+            }
+            Cbor::Major6(_, tag) => tag.do_to_diag(s, depth)?,
+            Cbor::Major7(_, sval) => s.push_str(&sval.to_diag()),
+            Cbor::Binary(_) => err_at!(FailCbor, msg: "cannot render a lazily-encoded Binary value as diagnostic notation")?,
+            Cbor::Tag(num, val) => {
+                s.push_str(&num.to_string());
+                s.push('(');
+                val.do_to_diag(s, depth + 1)?;
+                s.push(')');
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Abstract, allocation-free cursor over a byte source, modeled on the
+/// well known `bytes::Buf` contract. [Cbor::decode_buf] is generic over
+/// this trait so that callers streaming a filter or document off disk or
+/// a socket can drive decoding incrementally, a chunk at a time, instead
+/// of buffering the complete serialized value up front.
+pub trait Buf {
+    /// Number of bytes left to read.
+    fn remaining(&self) -> usize;
+
+    /// Borrow the bytes still left to read. For a cursor backed by
+    /// non-contiguous storage, this need only return the next contiguous
+    /// run; [Cbor::decode_buf] treats a short chunk the same as a buffer
+    /// that simply doesn't hold a complete value yet.
+    fn chunk(&self) -> &[u8];
+
+    /// Advance the read position by `cnt` bytes.
+    fn advance(&mut self, cnt: usize);
+}
+
+impl Buf for &[u8] {
+    fn remaining(&self) -> usize {
+        self.len()
+    }
+
+    fn chunk(&self) -> &[u8] {
+        self
+    }
+
+    fn advance(&mut self, cnt: usize) {
+        *self = &self[cnt..];
+    }
+}
+
+/// Result of asking a [Decoder] whether it holds a complete value yet.
+/// Shaped like `std::task::Poll`, minus the waker plumbing -- there's
+/// nothing here to wake; the caller just feeds more bytes and asks again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Poll<T> {
+    /// Not enough bytes have been fed yet to decode a complete value.
+    Pending,
+    /// A complete value is ready.
+    Ready(T),
+}
+
+/// Push-style counterpart to [Cbor::decode_buf], for transports -- a
+/// non-blocking socket, a chunked file-read -- that hand over bytes as
+/// they arrive instead of blocking an `io::Read`-er until a whole value
+/// is available.
+///
+/// `Decoder` owns a carry-over buffer of every byte fed to it that
+/// hasn't yet been consumed into a value, including a half-read header
+/// or additional-info field straddling two `feed` calls. Each [Decoder::feed]
+/// appends the new bytes and asks [Cbor::decode_buf] -- which walks the
+/// buffer with [scan_len]'s allocation-free scan, the same one
+/// [Cbor::decode_buf] itself uses -- whether a complete top-level value
+/// is now in hand. `scan_len`'s own recursion-depth parameter is what
+/// enforces [RECURSION_LIMIT] here, exactly as it does for [Cbor::decode].
+#[derive(Debug, Default)]
+pub struct Decoder {
+    carry: Vec<u8>,
+}
+
+impl Buf for Decoder {
+    fn remaining(&self) -> usize {
+        self.carry.len()
+    }
+
+    fn chunk(&self) -> &[u8] {
+        &self.carry
+    }
+
+    fn advance(&mut self, cnt: usize) {
+        self.carry.drain(..cnt);
+    }
+}
+
+impl Decoder {
+    /// Create an empty decoder, with nothing buffered yet.
+    pub fn new() -> Decoder {
+        Decoder::default()
+    }
+
+    /// Append `bytes` to the carry-over buffer and try to decode one
+    /// complete top-level value out of everything buffered so far.
+    ///
+    /// Returns [Poll::Pending] -- leaving the fed bytes buffered for the
+    /// next call -- until a complete value has arrived. Returns
+    /// [Poll::Ready] with the decoded value and the number of bytes it
+    /// consumed once one is available; any bytes left over, say the
+    /// start of the next value in a stream, stay buffered for the next
+    /// `feed`.
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Poll<(Cbor, usize)>> {
+        self.carry.extend_from_slice(bytes);
+        match Cbor::decode_buf(self)? {
+            None => Ok(Poll::Pending),
+            Some((val, n)) => Ok(Poll::Ready((val, n))),
+        }
+    }
+}
+
+// Peek, without consuming, the major-type/info/header-length of the
+// value starting at `buf`. Returns `Ok(None)` when `buf` doesn't yet hold
+// the full header (the leading byte, plus the 1/2/4/8 additional-info
+// bytes its `Info` width calls for).
+fn peek_hdr(buf: &[u8]) -> Result<Option<(u8, Info, usize)>> {
+    if buf.is_empty() {
+        return Ok(None);
+    }
+
+    let major = (buf[0] & 0xe0) >> 5;
+    let info: Info = (buf[0] & 0x1f).try_into()?;
+
+    let addnl_width = match info {
+        Info::Tiny(_) | Info::Indefinite => 0,
+        Info::U8 => 1,
+        Info::U16 => 2,
+        Info::U32 => 4,
+        Info::U64 => 8,
+        Info::Reserved28 | Info::Reserved29 | Info::Reserved30 => {
+            err_at!(FailCbor, msg: "no additional value")?
+        }
+    };
+    let hdr_len = 1 + addnl_width;
+    if buf.len() < hdr_len {
+        return Ok(None);
+    }
+
+    Ok(Some((major, info, hdr_len)))
+}
+
+// Parse the additional-info value out of `rest`, the bytes following the
+// leading header byte. `rest` must hold exactly as many bytes as `info`'s
+// width calls for, as guaranteed by a prior, successful `peek_hdr`.
+fn addnl_value(info: Info, rest: &[u8]) -> u64 {
+    match info {
+        Info::Tiny(num) => num as u64,
+        Info::U8 => rest[0] as u64,
+        Info::U16 => u16::from_be_bytes(rest[..2].try_into().unwrap()) as u64,
+        Info::U32 => u32::from_be_bytes(rest[..4].try_into().unwrap()) as u64,
+        Info::U64 => u64::from_be_bytes(rest[..8].try_into().unwrap()),
+        Info::Indefinite => 0,
+        _ => unreachable!(),
+    }
+}
+
+// Compute the number of bytes, starting at `buf`, needed to hold one
+// complete encoded Cbor value, without materializing it. Mirrors
+// `Cbor::do_decode`'s major-type dispatch, but only ever reads from the
+// slice already in hand, returning `Ok(None)` the moment it would need a
+// byte `buf` doesn't have.
+fn scan_len(buf: &[u8], depth: u32) -> Result<Option<usize>> {
+    if depth > RECURSION_LIMIT {
+        return err_at!(FailCbor, msg: "decode recursion limt exceeded");
+    }
+
+    let (major, info, hdr_len) = match peek_hdr(buf)? {
+        None => return Ok(None),
+        Some(hdr) => hdr,
+    };
+    let addnl_val = addnl_value(info, &buf[1..hdr_len]);
+
+    // A major7/tiny-31 byte, i.e. the `Break` stop-code, is always
+    // encoded as the single byte 0xff.
+    let is_break = |n: usize| buf.get(n).copied() == Some(0xff);
+
+    let len = match (major, info) {
+        (0, _) | (1, _) => hdr_len,
+        (2, Info::Indefinite) | (3, Info::Indefinite) => {
+            let mut n = hdr_len;
+            loop {
+                let brk = is_break(n);
+                match scan_len(&buf[n..], depth + 1)? {
+                    None => return Ok(None),
+                    Some(k) => n += k,
+                }
+                if brk {
+                    break;
+                }
+            }
+            n
+        }
+        (2, _) | (3, _) => {
+            let payload: usize = err_at!(FailConvert, addnl_val.try_into())?;
+            hdr_len + payload
+        }
+        (4, Info::Indefinite) => {
+            let mut n = hdr_len;
+            loop {
+                if is_break(n) {
+                    n += 1;
+                    break;
+                }
+                match scan_len(&buf[n..], depth + 1)? {
+                    None => return Ok(None),
+                    Some(k) => n += k,
+                }
+            }
+            n
+        }
+        (4, _) => {
+            let mut n = hdr_len;
+            for _ in 0..addnl_val {
+                match scan_len(&buf[n..], depth + 1)? {
+                    None => return Ok(None),
+                    Some(k) => n += k,
+                }
+            }
+            n
+        }
+        (5, Info::Indefinite) => {
+            let mut n = hdr_len;
+            loop {
+                if is_break(n) {
+                    n += 1;
+                    break;
+                }
+                for _ in 0..2 {
+                    match scan_len(&buf[n..], depth + 1)? {
+                        None => return Ok(None),
+                        Some(k) => n += k,
+                    }
+                }
+            }
+            n
+        }
+        (5, _) => {
+            let mut n = hdr_len;
+            for _ in 0..(addnl_val * 2) {
+                match scan_len(&buf[n..], depth + 1)? {
+                    None => return Ok(None),
+                    Some(k) => n += k,
+                }
+            }
+            n
+        }
+        (6, _) => match addnl_val {
+            // Every well-known tag `Tag::decode` recognizes -- 0/1
+            // (date/time), 2/3 (bignum), 21/22/23 (expected-encoding),
+            // 39 (Identifier), 42 (Link) -- wraps a nested Cbor value on
+            // the wire; only the catch-all `Tag::Value` consumes nothing
+            // beyond its own header.
+            0 | 1 | 2 | 3 | 21 | 22 | 23 | 39 | 42 => {
+                match scan_len(&buf[hdr_len..], depth + 1)? {
+                    None => return Ok(None),
+                    Some(k) => hdr_len + k,
+                }
+            }
+            _ => hdr_len,
+        },
+        (7, _) => hdr_len,
+        _ => unreachable!(),
+    };
+
+    if buf.len() < len {
+        Ok(None)
+    } else {
+        Ok(Some(len))
+    }
+}
+
+/// Borrowing counterpart of [Cbor], produced by [Cbor::decode_slice] for
+/// zero-copy decoding off a single contiguous, in-memory buffer.
+/// [CborRef::Major2]/[CborRef::Major3] borrow their payload directly
+/// from the input instead of copying it into an owned `Vec`/`String`;
+/// every other variant is cheap enough by value (an integer, a handful
+/// of header bytes, or a recursive collection of `CborRef` itself) that
+/// there's nothing to gain from borrowing it too. Indefinite-length
+/// byte/text strings are the one case that can't borrow a single
+/// contiguous slice -- their chunks are interleaved with per-chunk
+/// headers in the input -- so those fall back to an owned `Cow::Owned`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CborRef<'a> {
+    Major0(Info, u64),
+    Major1(Info, u64),
+    Major2(Info, Cow<'a, [u8]>),
+    Major3(Info, Cow<'a, str>),
+    Major4(Info, Vec<CborRef<'a>>),
+    Major5(Info, Vec<(Key, CborRef<'a>)>),
+    Major6(Info, Tag),
+    Major7(Info, SimpleValue),
+    Tag(u64, Box<CborRef<'a>>),
+}
+
+impl<'a> CborRef<'a> {
+    /// Lift this borrowed value back into the owned [Cbor] enum, copying
+    /// every [CborRef::Major2]/[CborRef::Major3] payload it doesn't
+    /// already own.
+    pub fn into_owned(self) -> Cbor {
+        match self {
+            CborRef::Major0(info, num) => Cbor::Major0(info, num),
+            CborRef::Major1(info, num) => Cbor::Major1(info, num),
+            CborRef::Major2(info, byts) => Cbor::Major2(info, byts.into_owned()),
+            CborRef::Major3(info, text) => {
+                Cbor::Major3(info, text.into_owned().into_bytes())
+            }
+            CborRef::Major4(info, list) => {
+                Cbor::Major4(info, list.into_iter().map(CborRef::into_owned).collect())
+            }
+            CborRef::Major5(info, map) => {
+                let map = map
+                    .into_iter()
+                    .map(|(key, val)| (key, val.into_owned()))
+                    .collect();
+                Cbor::Major5(info, map)
+            }
+            CborRef::Major6(info, tag) => Cbor::Major6(info, tag),
+            CborRef::Major7(info, sval) => Cbor::Major7(info, sval),
+            CborRef::Tag(num, val) => Cbor::Tag(num, Box::new(val.into_owned())),
+        }
+    }
+}
+
+// Zero-copy counterpart of `Cbor::do_decode`, walking `buf` by offset
+// instead of pulling from an `io::Read`-er. Mirrors its major-type
+// dispatch exactly, except [Major2]/[Major3] slice directly into `buf`
+// for the common, definite-length case.
+fn do_decode_slice(buf: &[u8], depth: u32) -> Result<(CborRef<'_>, usize)> {
+    if depth > RECURSION_LIMIT {
+        return err_at!(FailCbor, msg: "decode recursion limt exceeded");
+    }
+
+    let (major, info, hdr_len) = match peek_hdr(buf)? {
+        Some(hdr) => hdr,
+        None => err_at!(IOError, msg: "truncated cbor header")?,
+    };
+    let addnl_val = addnl_value(info, &buf[1..hdr_len]);
+
+    let (val, n) = match (major, info) {
+        (0, _) => (CborRef::Major0(info, addnl_val), hdr_len),
+        (1, _) => (CborRef::Major1(info, addnl_val), hdr_len),
+        (2, Info::Indefinite) => {
+            let mut data: Vec<u8> = Vec::new();
+            let mut n = hdr_len;
+            loop {
+                let (val, k) = do_decode_slice(&buf[n..], depth + 1)?;
+                n += k;
+                match val {
+                    CborRef::Major2(_, chunk) => data.extend_from_slice(&chunk),
+                    CborRef::Major7(_, SimpleValue::Break) => break,
+                    _ => err_at!(FailConvert, msg: "expected byte chunk")?,
+                }
+            }
+            (CborRef::Major2(info, Cow::Owned(data)), n)
+        }
+        (2, _) => {
+            let len: usize = err_at!(FailConvert, addnl_val.try_into())?;
+            let end = hdr_len + len;
+            if buf.len() < end {
+                err_at!(IOError, msg: "truncated cbor byte-string")?;
+            }
+            (CborRef::Major2(info, Cow::Borrowed(&buf[hdr_len..end])), end)
+        }
+        (3, Info::Indefinite) => {
+            let mut text = String::new();
+            let mut n = hdr_len;
+            loop {
+                let (val, k) = do_decode_slice(&buf[n..], depth + 1)?;
+                n += k;
+                match val {
+                    CborRef::Major3(_, chunk) => text.push_str(&chunk),
+                    CborRef::Major7(_, SimpleValue::Break) => break,
+                    _ => err_at!(FailConvert, msg: "expected byte chunk")?,
+                }
+            }
+            (CborRef::Major3(info, Cow::Owned(text)), n)
+        }
+        (3, _) => {
+            let len: usize = err_at!(FailConvert, addnl_val.try_into())?;
+            let end = hdr_len + len;
+            if buf.len() < end {
+                err_at!(IOError, msg: "truncated cbor text")?;
+            }
+            let text = err_at!(FailConvert, std::str::from_utf8(&buf[hdr_len..end]))?;
+            (CborRef::Major3(info, Cow::Borrowed(text)), end)
+        }
+        (4, Info::Indefinite) => {
+            let mut list = vec![];
+            let mut n = hdr_len;
+            loop {
+                let (val, k) = do_decode_slice(&buf[n..], depth + 1)?;
+                n += k;
+                match val {
+                    CborRef::Major7(_, SimpleValue::Break) => break,
+                    item => list.push(item),
+                }
+            }
+            (CborRef::Major4(info, list), n)
+        }
+        (4, _) => {
+            let mut list = vec![];
+            let mut n = hdr_len;
+            for _ in 0..addnl_val {
+                let (val, k) = do_decode_slice(&buf[n..], depth + 1)?;
+                n += k;
+                list.push(val);
+            }
+            (CborRef::Major4(info, list), n)
+        }
+        (5, Info::Indefinite) => {
+            // As in `Cbor::do_decode`, break can only appear where the
+            // next key would be, so check for it right after decoding
+            // `key`, before attempting to decode a paired value.
+            let mut map = vec![];
+            let mut n = hdr_len;
+            loop {
+                let (key, j) = do_decode_slice(&buf[n..], depth + 1)?;
+                n += j;
+                if matches!(key, CborRef::Major7(_, SimpleValue::Break)) {
+                    break;
+                }
+                let (val, k) = do_decode_slice(&buf[n..], depth + 1)?;
+                n += k;
+                map.push((Key::from_cbor(key.into_owned())?, val));
+            }
+            (CborRef::Major5(info, map), n)
+        }
+        (5, _) => {
+            let mut map = vec![];
+            let mut n = hdr_len;
+            for _ in 0..addnl_val {
+                let (key, j) = do_decode_slice(&buf[n..], depth + 1)?;
+                n += j;
+                let (val, k) = do_decode_slice(&buf[n..], depth + 1)?;
+                n += k;
+                map.push((Key::from_cbor(key.into_owned())?, val));
+            }
+            (CborRef::Major5(info, map), n)
+        }
+        (6, _) => {
+            // `Tag::decode`, like `decode_hdr`, expects a cursor
+            // positioned right after the single leading header byte --
+            // it reads the additional-info bytes itself -- not after
+            // `hdr_len`, which (for major0/1/6) already counts them.
+            // Reuse it rather than duplicating its tag-39 special case.
+            let mut cursor = &buf[1..];
+            let (tag, m) = Tag::decode(info, &mut cursor, depth, &UNBOUNDED_DECODE_CONFIG)?;
+            (CborRef::Major6(info, tag), 1 + m)
+        }
+        (7, _) => {
+            // Unlike the other majors, a major7 `Info::U8/16/32/64`
+            // doesn't carry an additional-info *value* at all -- those
+            // bytes, counted into `hdr_len` by `peek_hdr`, are the
+            // simple-value's own payload, read directly by
+            // `SimpleValue::decode` from just past the leading byte.
+            let mut cursor = &buf[1..];
+            let (sval, m) = SimpleValue::decode(info, &mut cursor)?;
+            (CborRef::Major7(info, sval), 1 + m)
+        }
+        _ => unreachable!(),
+    };
+
+    Ok((val, n))
 }
 
 /// 5-bit value for additional info. Refer to Cbor [spec] for details.
@@ -635,7 +1613,7 @@ impl IntoCbor for SimpleValue {
             val @ Null => Cbor::Major7(Info::Tiny(22), val),
             Undefined => err_at!(FailConvert, msg: "simple-value-undefined")?,
             Reserved24(_) => err_at!(FailConvert, msg: "simple-value-unassigned1")?,
-            F16(_) => err_at!(FailConvert, msg: "simple-value-f16")?,
+            val @ F16(_) => Cbor::Major7(Info::U16, val),
             val @ F32(_) => Cbor::Major7(Info::U32, val),
             val @ F64(_) => Cbor::Major7(Info::U64, val),
             val @ Break => Cbor::Major7(Info::Indefinite, val),
@@ -663,6 +1641,112 @@ impl SimpleValue {
         }
     }
 
+    /// Widen an IEEE 754 binary16 bit-pattern to binary32. Exact: every
+    /// finite, zero, infinite or NaN `f16` value has a precise `f32`
+    /// counterpart, so this never rounds.
+    pub fn f16_to_f32(bits: u16) -> f32 {
+        let sign = u32::from(bits & 0x8000) << 16;
+        let exp = (bits & 0x7c00) >> 10;
+        let mantissa = u32::from(bits & 0x03ff);
+
+        let bits32 = if exp == 0 {
+            if mantissa == 0 {
+                sign // zero
+            } else {
+                // Subnormal: normalize by shifting the mantissa left until
+                // its leading bit lands at the implicit-bit position,
+                // counting the shifts to derive the binary32 exponent.
+                let mut mantissa = mantissa;
+                let mut e: i32 = -1;
+                loop {
+                    mantissa <<= 1;
+                    e += 1;
+                    if mantissa & 0x0400 != 0 {
+                        break;
+                    }
+                }
+                let mantissa = mantissa & 0x03ff;
+                let exp32 = (127 - 15 - e) as u32;
+                sign | (exp32 << 23) | (mantissa << 13)
+            }
+        } else if exp == 0x1f {
+            sign | 0x7f80_0000 | (mantissa << 13) // infinity or NaN
+        } else {
+            let exp32 = u32::from(exp) + (127 - 15);
+            sign | (exp32 << 23) | (mantissa << 13)
+        };
+        f32::from_bits(bits32)
+    }
+
+    /// Narrow an IEEE 754 binary32 value to the nearest binary16
+    /// bit-pattern, truncating (not rounding) the mantissa. Values that
+    /// don't fit -- too large, or too small to survive as a subnormal --
+    /// saturate to infinity or zero respectively. Callers that need an
+    /// exact result, e.g. [SimpleValue::from_f64_smallest], round-trip
+    /// the bits back through [SimpleValue::f16_to_f32] to check.
+    pub fn f32_to_f16(f: f32) -> u16 {
+        let bits = f.to_bits();
+        let sign = ((bits >> 16) & 0x8000) as u16;
+        let exp = ((bits >> 23) & 0xff) as i32;
+        let mantissa = bits & 0x007f_ffff;
+
+        if exp == 0xff {
+            // infinity, or NaN with its payload dropped to a single
+            // quiet-bit -- `f16` has no room to carry one.
+            return if mantissa == 0 {
+                sign | 0x7c00
+            } else {
+                sign | 0x7e00
+            };
+        }
+        if exp == 0 && mantissa == 0 {
+            return sign; // zero
+        }
+
+        let half_exp = exp - 127 + 15;
+        if half_exp >= 0x1f {
+            sign | 0x7c00 // overflow -> infinity
+        } else if half_exp <= 0 {
+            if half_exp < -10 {
+                sign // underflows even a subnormal -> zero
+            } else {
+                let mantissa = mantissa | 0x0080_0000; // restore implicit bit
+                let shift = (14 - half_exp) as u32;
+                sign | ((mantissa >> shift) as u16)
+            }
+        } else {
+            sign | ((half_exp as u16) << 10) | ((mantissa >> 13) as u16)
+        }
+    }
+
+    /// Return the narrowest of `F16`/`F32`/`F64` that represents `f`
+    /// exactly, verified by round-tripping the candidate back to `f64`
+    /// and comparing with `==`. Used by [Cbor::encode_canonical] to
+    /// pick the smallest-width float encoding.
+    pub fn from_f64_smallest(f: f64) -> SimpleValue {
+        if f.is_nan() {
+            // `NaN == NaN` is always false in IEEE-754, so every other
+            // arm below (which narrows by round-tripping and comparing
+            // with `==`) can never pick a width for a NaN and would
+            // always fall through to `F64`. Canonicalize every NaN,
+            // whatever its sign or payload, to RFC 8949's canonical NaN:
+            // the half-precision quiet NaN with a zero payload.
+            return SimpleValue::F16(0x7e00);
+        }
+
+        let as_f32 = f as f32;
+        if f64::from(as_f32) != f {
+            return SimpleValue::F64(f);
+        }
+
+        let bits16 = Self::f32_to_f16(as_f32);
+        if Self::f16_to_f32(bits16) == as_f32 {
+            SimpleValue::F16(bits16)
+        } else {
+            SimpleValue::F32(as_f32)
+        }
+    }
+
     fn encode<W>(sval: &SimpleValue, w: &mut W) -> Result<usize>
     where
         W: io::Write,
@@ -705,7 +1789,11 @@ impl SimpleValue {
             Info::Tiny(23) => err_at!(FailCbor, msg: "simple-value-undefined")?,
             Info::Tiny(_) => err_at!(FailCbor, msg: "simple-value-unassigned")?,
             Info::U8 => err_at!(FailCbor, msg: "simple-value-unassigned1")?,
-            Info::U16 => err_at!(FailCbor, msg: "simple-value-f16")?,
+            Info::U16 => {
+                read_r!(r, &mut scratch[..2]);
+                let bits = u16::from_be_bytes(scratch[..2].try_into().unwrap());
+                (SimpleValue::F16(bits), 2)
+            }
             Info::U32 => {
                 read_r!(r, &mut scratch[..4]);
                 let val = f32::from_be_bytes(scratch[..4].try_into().unwrap());
@@ -723,22 +1811,110 @@ impl SimpleValue {
         };
         Ok((val, n))
     }
+
+    /// Render `self` as an [RFC 8949 §8] diagnostic-notation token, e.g.
+    /// `true`, `null`, or `3.14`.
+    ///
+    /// [RFC 8949 §8]: https://www.rfc-editor.org/rfc/rfc8949.html#section-8
+    fn to_diag(self) -> String {
+        match self {
+            SimpleValue::Unassigned => "unassigned".to_string(),
+            SimpleValue::True => "true".to_string(),
+            SimpleValue::False => "false".to_string(),
+            SimpleValue::Null => "null".to_string(),
+            SimpleValue::Undefined => "undefined".to_string(),
+            SimpleValue::Reserved24(val) => format!("simple({})", val),
+            SimpleValue::F16(bits) => diag_float(f64::from(Self::f16_to_f32(bits))),
+            SimpleValue::F32(f) => diag_float(f64::from(f)),
+            SimpleValue::F64(f) => diag_float(f),
+            SimpleValue::Break => "break".to_string(),
+        }
+    }
+}
+
+// Render a float the way diagnostic notation expects: "NaN"/"Infinity"/
+// "-Infinity" for the non-finite cases, and otherwise the shortest
+// round-tripping decimal with a `.` (or exponent) so it can't be
+// mistaken for an integer.
+fn diag_float(f: f64) -> String {
+    if f.is_nan() {
+        return "NaN".to_string();
+    } else if f.is_infinite() {
+        return if f > 0.0 { "Infinity".to_string() } else { "-Infinity".to_string() };
+    }
+
+    let text = f.to_string();
+    if text.contains('.') || text.contains('e') || text.contains('E') {
+        text
+    } else {
+        format!("{}.0", text)
+    }
 }
 
 /// Major type 6, Tag values. Refer to Cbor [spec] for details.
 ///
 /// [spec]: https://tools.ietf.org/html/rfc7049
-#[derive(Debug, Clone, Eq, PartialEq, Arbitrary)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Tag {
     /// Tag 39, used as identifier marker. This implementation shall
     /// treat them as literal values. Used by `Cborize` procedural
     /// macro to match values with types.
     Identifier(Box<Cbor>),
+    /// Tag 0: an RFC 3339 date-time string, e.g.
+    /// `"2023-01-15T18:30:00Z"`.
+    DateTime(SystemTime),
+    /// Tag 1: seconds since the Unix epoch, as an integer or a float.
+    /// Always encoded as a float on the way out, since that's the one
+    /// form that never truncates a sub-second component; either form
+    /// is accepted coming in.
+    EpochTime(SystemTime),
+    /// Tag 2 (unsigned) or tag 3 (negative): an arbitrary-width integer
+    /// beyond `u64`/`i64`'s range, see [BigInt].
+    BigNum(BigInt),
+    /// Tag 21/22/23: a hint that the nested value, once decoded, is
+    /// best displayed as base64url/base64/base16 text. This
+    /// implementation round-trips the hint and the nested value as-is;
+    /// it doesn't perform the re-encoding itself.
+    ExpectedEncoding(Encoding, Box<Cbor>),
+    /// Tag 42: an IPLD/DAG-CBOR content-addressed link, see [Cid].
+    Link(Cid),
     /// Catch all tag-value, follows the generic Tag specification
     /// for Cbor.
     Value(u64),
 }
 
+impl arbitrary::Arbitrary for Tag {
+    fn arbitrary(u: &mut Unstructured) -> arbitrary::Result<Self> {
+        // `SystemTime` has no `Arbitrary` impl of its own, so the
+        // date/time variants are built by hand from an arbitrary,
+        // bounded offset from the epoch instead of deriving this impl.
+        let tag = match u.arbitrary::<u8>()? % 7 {
+            0 => Tag::Identifier(Box::new(u.arbitrary()?)),
+            1 => {
+                let secs = u64::from(u.arbitrary::<u32>()?);
+                Tag::DateTime(UNIX_EPOCH + Duration::from_secs(secs))
+            }
+            2 => {
+                let secs = u64::from(u.arbitrary::<u32>()?);
+                Tag::EpochTime(UNIX_EPOCH + Duration::from_secs(secs))
+            }
+            3 => Tag::BigNum(u.arbitrary()?),
+            4 => {
+                let encoding = *u.choose(&[
+                    Encoding::Base64Url,
+                    Encoding::Base64,
+                    Encoding::Base16,
+                ])?;
+                Tag::ExpectedEncoding(encoding, Box::new(u.arbitrary()?))
+            }
+            5 => Tag::Link(u.arbitrary()?),
+            6 => Tag::Value(u.arbitrary()?),
+            _ => unreachable!(),
+        };
+        Ok(tag)
+    }
+}
+
 impl From<Tag> for Cbor {
     fn from(tag: Tag) -> Cbor {
         let num = tag.to_tag_value();
@@ -761,6 +1937,12 @@ impl Tag {
     pub fn to_tag_value(&self) -> u64 {
         match self {
             Tag::Identifier(_) => 39,
+            Tag::DateTime(_) => 0,
+            Tag::EpochTime(_) => 1,
+            Tag::BigNum(num) if num.negative => 3,
+            Tag::BigNum(_) => 2,
+            Tag::ExpectedEncoding(encoding, _) => encoding.to_tag_value(),
+            Tag::Link(_) => 42,
             Tag::Value(val) => *val,
         }
     }
@@ -773,26 +1955,430 @@ impl Tag {
         let mut n = encode_addnl(num, w)?;
         n += match tag {
             Tag::Identifier(val) => val.encode(w)?,
+            Tag::DateTime(time) => {
+                let text = format_rfc3339(*time)?;
+                let info = err_at!(FailConvert, u64::try_from(text.len()))?.into();
+                Cbor::Major3(info, text.into_bytes()).encode(w)?
+            }
+            Tag::EpochTime(time) => epoch_secs_cbor(*time)?.encode(w)?,
+            Tag::BigNum(num) => {
+                let info = err_at!(FailConvert, u64::try_from(num.magnitude.len()))?.into();
+                Cbor::Major2(info, num.magnitude.clone()).encode(w)?
+            }
+            Tag::ExpectedEncoding(_, val) => val.encode(w)?,
+            Tag::Link(cid) => {
+                let mut bytes = Vec::with_capacity(1 + cid.bytes.len());
+                bytes.push(0); // multibase-identity prefix, per the DAG-CBOR convention
+                bytes.extend_from_slice(&cid.bytes);
+                let info = err_at!(FailConvert, u64::try_from(bytes.len()))?.into();
+                Cbor::Major2(info, bytes).encode(w)?
+            }
             Tag::Value(_) => 0,
         };
 
         Ok(n)
     }
 
-    fn decode<R>(info: Info, r: &mut R) -> Result<(Tag, usize)>
+    fn decode<R>(info: Info, r: &mut R, depth: u32, cfg: &DecodeConfig) -> Result<(Tag, usize)>
     where
         R: io::Read,
     {
         let (tag, n) = decode_addnl(info, r)?;
         let (tag, m) = match tag {
+            0 => {
+                let (val, m) = Cbor::do_decode(r, depth + 1, cfg)?;
+                let text = match val {
+                    Cbor::Major3(_, bytes) => err_at!(FailConvert, String::from_utf8(bytes))?,
+                    _ => err_at!(FailCbor, msg: "tag-0 value is not text")?,
+                };
+                (Tag::DateTime(parse_rfc3339(&text)?), m)
+            }
+            1 => {
+                let (val, m) = Cbor::do_decode(r, depth + 1, cfg)?;
+                let secs = match val {
+                    Cbor::Major0(_, n) => n as f64,
+                    Cbor::Major1(_, n) => -(1.0 + n as f64),
+                    Cbor::Major7(_, SimpleValue::F64(f)) => f,
+                    Cbor::Major7(_, SimpleValue::F32(f)) => f64::from(f),
+                    _ => err_at!(FailCbor, msg: "tag-1 value is not a number")?,
+                };
+                let time = if secs >= 0.0 {
+                    UNIX_EPOCH + Duration::from_secs_f64(secs)
+                } else {
+                    UNIX_EPOCH - Duration::from_secs_f64(-secs)
+                };
+                (Tag::EpochTime(time), m)
+            }
+            num @ 2 | num @ 3 => {
+                let (val, m) = Cbor::do_decode(r, depth + 1, cfg)?;
+                let magnitude = match val {
+                    Cbor::Major2(_, bytes) => bytes,
+                    _ => err_at!(FailCbor, msg: "tag-{} value is not a byte string", num)?,
+                };
+                (Tag::BigNum(BigInt::new(num == 3, magnitude)), m)
+            }
+            num @ 21 | num @ 22 | num @ 23 => {
+                let (val, m) = Cbor::do_decode(r, depth + 1, cfg)?;
+                let encoding = Encoding::from_tag_value(num)?;
+                (Tag::ExpectedEncoding(encoding, Box::new(val)), m)
+            }
             39 => {
-                let (val, m) = Cbor::decode(r)?;
+                let (val, m) = Cbor::do_decode(r, depth + 1, cfg)?;
                 (Tag::Identifier(Box::new(val)), m)
             }
+            42 => {
+                let (val, m) = Cbor::do_decode(r, depth + 1, cfg)?;
+                let bytes = match val {
+                    Cbor::Major2(_, bytes) => bytes,
+                    _ => err_at!(FailCbor, msg: "tag-42 value is not a byte string")?,
+                };
+                match bytes.split_first() {
+                    Some((0, cid)) => (Tag::Link(Cid::new(cid.to_vec())), m),
+                    Some(_) => err_at!(FailCbor, msg: "tag-42 link missing multibase-identity prefix")?,
+                    None => err_at!(FailCbor, msg: "tag-42 link is empty")?,
+                }
+            }
             val => (Tag::Value(val), 0),
         };
         Ok((tag, m + n))
     }
+
+    // Render `self` as diagnostic notation, `<num>(<item>)`. `Tag::Value`
+    // is the one exception: this codec's catch-all tag never wraps a
+    // nested item (see `Tag::decode`'s final arm), so it renders bare.
+    fn do_to_diag(&self, s: &mut String, depth: u32) -> Result<()> {
+        match self {
+            Tag::Identifier(val) => {
+                s.push_str("39(");
+                val.do_to_diag(s, depth + 1)?;
+                s.push(')');
+            }
+            Tag::DateTime(time) => {
+                let text = format_rfc3339(*time)?;
+                s.push_str("0(");
+                s.push_str(&format!("{:?}", text));
+                s.push(')');
+            }
+            Tag::EpochTime(time) => {
+                s.push_str("1(");
+                match epoch_secs_cbor(*time)? {
+                    Cbor::Major7(_, sval) => s.push_str(&sval.to_diag()),
+                    _ => unreachable!(),
+                }
+                s.push(')');
+            }
+            Tag::BigNum(num) => {
+                s.push_str(&self.to_tag_value().to_string());
+                s.push_str("(h'");
+                for byt in num.magnitude.iter() {
+                    s.push_str(&format!("{:02x}", byt));
+                }
+                s.push_str("')");
+            }
+            Tag::ExpectedEncoding(encoding, val) => {
+                s.push_str(&encoding.to_tag_value().to_string());
+                s.push('(');
+                val.do_to_diag(s, depth + 1)?;
+                s.push(')');
+            }
+            Tag::Link(cid) => {
+                s.push_str("42(h'00");
+                for byt in cid.bytes.iter() {
+                    s.push_str(&format!("{:02x}", byt));
+                }
+                s.push_str("')");
+            }
+            Tag::Value(num) => s.push_str(&num.to_string()),
+        }
+        Ok(())
+    }
+}
+
+/// RFC 8949 §3.4.5 "Expected later encoding for CBOR-to-JSON
+/// conversion" hint, carried by [Tag::ExpectedEncoding].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Encoding {
+    /// Tag 21.
+    Base64Url,
+    /// Tag 22.
+    Base64,
+    /// Tag 23.
+    Base16,
+}
+
+impl Encoding {
+    fn to_tag_value(self) -> u64 {
+        match self {
+            Encoding::Base64Url => 21,
+            Encoding::Base64 => 22,
+            Encoding::Base16 => 23,
+        }
+    }
+
+    fn from_tag_value(num: u64) -> Result<Encoding> {
+        let encoding = match num {
+            21 => Encoding::Base64Url,
+            22 => Encoding::Base64,
+            23 => Encoding::Base16,
+            num => err_at!(FailCbor, msg: "{} is not an expected-encoding tag", num)?,
+        };
+        Ok(encoding)
+    }
+}
+
+/// Tag 2 (unsigned) or tag 3 (negative) bignum: an arbitrary-width
+/// integer beyond what `u64`/`i64` can hold, carried as its sign and
+/// big-endian magnitude bytes. Tag 3's value is `-1 - magnitude`, per
+/// [spec]; `negative` records which tag `self` came from (or will
+/// encode as) rather than pre-adjusting `magnitude` by that `-1`.
+///
+/// [spec]: https://tools.ietf.org/html/rfc7049#section-2.4.2
+#[derive(Debug, Clone, Eq, PartialEq, Arbitrary)]
+pub struct BigInt {
+    pub negative: bool,
+    pub magnitude: Vec<u8>,
+}
+
+impl BigInt {
+    /// Construct from a sign and big-endian magnitude bytes.
+    pub fn new(negative: bool, magnitude: Vec<u8>) -> BigInt {
+        BigInt { negative, magnitude }
+    }
+
+    /// Widen an `i128` into the sign/magnitude form `BigInt` stores.
+    pub fn from_i128(value: i128) -> BigInt {
+        let (negative, magnitude) = if value < 0 {
+            (true, (-1 - value) as u128)
+        } else {
+            (false, value as u128)
+        };
+        let bytes = magnitude.to_be_bytes();
+        let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len() - 1);
+        BigInt::new(negative, bytes[start..].to_vec())
+    }
+
+    /// Narrow back to an `i128`, failing if the magnitude is wider than
+    /// `i128` can represent.
+    pub fn to_i128(&self) -> Result<i128> {
+        if self.magnitude.len() > 16 {
+            err_at!(FailConvert, msg: "bignum magnitude too wide for i128")?;
+        }
+        let mut scratch = [0_u8; 16];
+        let start = 16 - self.magnitude.len();
+        scratch[start..].copy_from_slice(&self.magnitude);
+        let magnitude = u128::from_be_bytes(scratch);
+
+        if magnitude > (i128::MAX as u128) {
+            err_at!(FailConvert, msg: "bignum magnitude too wide for i128")?;
+        }
+        let magnitude = magnitude as i128;
+        Ok(if self.negative { -1 - magnitude } else { magnitude })
+    }
+}
+
+impl IntoCbor for BigInt {
+    fn into_cbor(self) -> Result<Cbor> {
+        Ok(Tag::BigNum(self).into())
+    }
+}
+
+impl FromCbor for BigInt {
+    fn from_cbor(val: Cbor) -> Result<BigInt> {
+        match val {
+            Cbor::Major6(_, Tag::BigNum(num)) => Ok(num),
+            _ => err_at!(FailConvert, msg: "not a bignum tag"),
+        }
+    }
+}
+
+/// An [IPLD](https://ipld.io/) content identifier, carried by
+/// [Tag::Link] (CBOR tag 42). This implementation treats a CID as an
+/// opaque bag of bytes -- the version/codec/multihash varints packed
+/// into it are someone else's concern -- and only handles the
+/// DAG-CBOR framing: on the wire, tag 42 wraps a byte-string whose
+/// first byte is always the multibase "identity" prefix `0x00`,
+/// followed by `bytes` itself.
+#[derive(Debug, Clone, Eq, PartialEq, Arbitrary)]
+pub struct Cid {
+    pub bytes: Vec<u8>,
+}
+
+impl Cid {
+    /// Construct from the raw binary CID, without the multibase-identity
+    /// prefix tag 42's wire form adds around it.
+    pub fn new(bytes: Vec<u8>) -> Cid {
+        Cid { bytes }
+    }
+}
+
+impl IntoCbor for Cid {
+    fn into_cbor(self) -> Result<Cbor> {
+        Ok(Tag::Link(self).into())
+    }
+}
+
+impl FromCbor for Cid {
+    fn from_cbor(val: Cbor) -> Result<Cid> {
+        match val {
+            Cbor::Major6(_, Tag::Link(cid)) => Ok(cid),
+            _ => err_at!(FailConvert, msg: "not a link tag"),
+        }
+    }
+}
+
+impl IntoCbor for SystemTime {
+    fn into_cbor(self) -> Result<Cbor> {
+        Ok(Tag::EpochTime(self).into())
+    }
+}
+
+impl FromCbor for SystemTime {
+    fn from_cbor(val: Cbor) -> Result<SystemTime> {
+        match val {
+            Cbor::Major6(_, Tag::DateTime(time)) => Ok(time),
+            Cbor::Major6(_, Tag::EpochTime(time)) => Ok(time),
+            _ => err_at!(FailConvert, msg: "not a date/time tag"),
+        }
+    }
+}
+
+// Wrap `time`'s offset from the epoch as the `Cbor` value tag-1
+// expects: a float, signed for times before 1970, matching
+// `Tag::decode`'s tag-1 arm.
+fn epoch_secs_cbor(time: SystemTime) -> Result<Cbor> {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(dur) => dur.as_secs_f64(),
+        Err(err) => -err.duration().as_secs_f64(),
+    };
+    Ok(Cbor::Major7(Info::U64, SimpleValue::F64(secs)))
+}
+
+// Format `time` as an RFC 3339 UTC date-time string, e.g.
+// "2023-01-15T18:30:00Z". Sub-second precision is dropped: CBOR
+// producers that need it should prefer tag-1 (epoch time) instead.
+fn format_rfc3339(time: SystemTime) -> Result<String> {
+    let (days, secs_of_day) = match time.duration_since(UNIX_EPOCH) {
+        Ok(dur) => ((dur.as_secs() / 86_400) as i64, dur.as_secs() % 86_400),
+        Err(err) => {
+            let dur = err.duration();
+            let secs = dur.as_secs();
+            let days_before = secs.div_ceil(86_400);
+            let rem = (days_before * 86_400) - secs;
+            (-(days_before as i64), rem)
+        }
+    };
+    let (year, month, day) = civil_from_days(days);
+    let (hour, minute, second) = (secs_of_day / 3600, (secs_of_day / 60) % 60, secs_of_day % 60);
+
+    Ok(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    ))
+}
+
+// Converse of [format_rfc3339]. Accepts the `Z`-suffixed UTC form that
+// this codec itself produces, plus the numeric `+HH:MM`/`-HH:MM` offset
+// form RFC 3339 also allows; rejects anything else, including leap
+// seconds (`:60`).
+fn parse_rfc3339(text: &str) -> Result<SystemTime> {
+    fn bad<T>(text: &str) -> Result<T> {
+        err_at!(FailConvert, msg: "{:?} is not an RFC-3339 date-time", text)
+    }
+
+    if text.len() < 20 {
+        return bad(text);
+    }
+    let bytes = text.as_bytes();
+    let digit = |n: usize| -> Result<i64> {
+        if bytes[n].is_ascii_digit() {
+            Ok(i64::from(bytes[n] - b'0'))
+        } else {
+            bad(text)
+        }
+    };
+    let num2 = |n: usize| -> Result<i64> { Ok(digit(n)? * 10 + digit(n + 1)?) };
+
+    if bytes[4] != b'-' || bytes[7] != b'-' || bytes[10] != b'T' {
+        return bad(text);
+    }
+    let year = digit(0)? * 1000 + digit(1)? * 100 + num2(2)?;
+    let month = num2(5)?;
+    let day = num2(8)?;
+    if bytes[13] != b':' || bytes[16] != b':' {
+        return bad(text);
+    }
+    let hour = num2(11)?;
+    let minute = num2(14)?;
+    let second = num2(17)?;
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return bad(text);
+    }
+    if hour > 23 || minute > 59 || second > 59 {
+        return bad(text);
+    }
+
+    let rest = &text[19..];
+    let offset_secs = match rest.as_bytes().first() {
+        Some(b'Z') if rest.len() == 1 => 0,
+        Some(b'+') | Some(b'-') if rest.len() == 6 && rest.as_bytes()[3] == b':' => {
+            let sign = if rest.as_bytes()[0] == b'+' { 1 } else { -1 };
+            let off_bytes = rest.as_bytes();
+            let off_digit = |n: usize| -> Result<i64> {
+                if off_bytes[n].is_ascii_digit() {
+                    Ok(i64::from(off_bytes[n] - b'0'))
+                } else {
+                    bad(text)
+                }
+            };
+            let off_hour = off_digit(1)? * 10 + off_digit(2)?;
+            let off_minute = off_digit(4)? * 10 + off_digit(5)?;
+            sign * (off_hour * 3600 + off_minute * 60)
+        }
+        _ => return bad(text),
+    };
+
+    let days = days_from_civil(year, month as u32, day as u32);
+    let secs_of_day = hour * 3600 + minute * 60 + second;
+    let total_secs = days * 86_400 + secs_of_day - offset_secs;
+
+    let time = if total_secs >= 0 {
+        UNIX_EPOCH + Duration::from_secs(total_secs as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs((-total_secs) as u64)
+    };
+    Ok(time)
+}
+
+// Howard Hinnant's well-known, widely republished `civil_from_days`
+// algorithm: the Gregorian (year, month, day) a given day-count,
+// relative to the 1970-01-01 epoch, falls on.
+//
+// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(z: i64) -> (i64, u32, u32) {
+    let z = z + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+// Converse of [civil_from_days]: day-count, relative to the epoch, a
+// given Gregorian (year, month, day) falls on.
+//
+// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = if y >= 0 { y } else { y - 399 } / 400;
+    let yoe = y - era * 400;
+    let mp = i64::from((m + 9) % 12);
+    let doy = (153 * mp + 2) / 5 + i64::from(d) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
 }
 
 /// Possible types that can be used as a key in cbor-map.
@@ -899,6 +2485,25 @@ impl PartialOrd for Key {
     }
 }
 
+impl hash::Hash for Key {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        use Key::*;
+
+        self.to_type_order().hash(state);
+        match self {
+            Bool(key) => key.hash(state),
+            N64(key) => key.hash(state),
+            U64(key) => key.hash(state),
+            // total_cmp()/Eq compare float keys bit-for-bit, so hash the
+            // same bit pattern to stay consistent with Eq.
+            F32(key) => key.to_bits().hash(state),
+            F64(key) => key.to_bits().hash(state),
+            Bytes(key) => key.hash(state),
+            Text(key) => key.hash(state),
+        }
+    }
+}
+
 impl IntoCbor for Key {
     fn into_cbor(self) -> Result<Cbor> {
         let val = match self {
@@ -1241,6 +2846,247 @@ impl FromCbor for Vec<(Key, Cbor)> {
     }
 }
 
+/// Strict counterpart to [FromCbor] for map-like collections. Unlike
+/// [FromCbor], which resolves duplicate keys in a [Cbor] map as
+/// last-entry-wins, `from_cbor_strict` rejects any input with a
+/// repeated key.
+pub trait FromCborStrict: Sized {
+    fn from_cbor_strict(val: Cbor) -> Result<Self>;
+}
+
+impl<T> IntoCbor for BTreeMap<Key, T>
+where
+    T: IntoCbor,
+{
+    fn into_cbor(self) -> Result<Cbor> {
+        self.into_iter()
+            .map(|(key, val)| Ok((key, val.into_cbor()?)))
+            .collect::<Result<Vec<(Key, Cbor)>>>()?
+            .into_cbor()
+    }
+}
+
+impl<T> FromCbor for BTreeMap<Key, T>
+where
+    T: FromCbor,
+{
+    // Duplicate keys are resolved last-entry-wins: entries are inserted
+    // in stream order, so a later occurrence simply overrides an earlier
+    // one. Callers that must reject duplicates should use
+    // `from_cbor_strict` instead.
+    fn from_cbor(val: Cbor) -> Result<Self> {
+        let items = Vec::<(Key, Cbor)>::from_cbor(val)?;
+        let mut map = BTreeMap::new();
+        for (key, val) in items.into_iter() {
+            map.insert(key, T::from_cbor(val)?);
+        }
+        Ok(map)
+    }
+}
+
+impl<T> FromCborStrict for BTreeMap<Key, T>
+where
+    T: FromCbor,
+{
+    fn from_cbor_strict(val: Cbor) -> Result<Self> {
+        let items = Vec::<(Key, Cbor)>::from_cbor(val)?;
+        let mut map = BTreeMap::new();
+        for (key, val) in items.into_iter() {
+            let val = T::from_cbor(val)?;
+            if map.insert(key.clone(), val).is_some() {
+                err_at!(FailConvert, msg: "duplicate key {:?} in cbor map", key)?;
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl<T> IntoCbor for HashMap<Key, T>
+where
+    T: IntoCbor,
+{
+    fn into_cbor(self) -> Result<Cbor> {
+        self.into_iter()
+            .map(|(key, val)| Ok((key, val.into_cbor()?)))
+            .collect::<Result<Vec<(Key, Cbor)>>>()?
+            .into_cbor()
+    }
+}
+
+impl<T> FromCbor for HashMap<Key, T>
+where
+    T: FromCbor,
+{
+    // See the `BTreeMap<Key, T>` impl above for the duplicate-key policy.
+    fn from_cbor(val: Cbor) -> Result<Self> {
+        let items = Vec::<(Key, Cbor)>::from_cbor(val)?;
+        let mut map = HashMap::new();
+        for (key, val) in items.into_iter() {
+            map.insert(key, T::from_cbor(val)?);
+        }
+        Ok(map)
+    }
+}
+
+impl<T> FromCborStrict for HashMap<Key, T>
+where
+    T: FromCbor,
+{
+    fn from_cbor_strict(val: Cbor) -> Result<Self> {
+        let items = Vec::<(Key, Cbor)>::from_cbor(val)?;
+        let mut map = HashMap::new();
+        for (key, val) in items.into_iter() {
+            let val = T::from_cbor(val)?;
+            if map.insert(key.clone(), val).is_some() {
+                err_at!(FailConvert, msg: "duplicate key {:?} in cbor map", key)?;
+            }
+        }
+        Ok(map)
+    }
+}
+
+impl Diff for Cbor {
+    type Delta = Cbor;
+
+    fn diff(&self, old: &Cbor) -> Self::Delta {
+        old.clone()
+    }
+
+    fn merge(&self, delta: &Self::Delta) -> Self {
+        delta.clone()
+    }
+}
+
+impl Merge3 for Cbor {
+    /// Reconcile two divergent [Cbor] values against their common
+    /// ancestor `base`.
+    ///
+    /// [Cbor::Major4] (array) and [Cbor::Major5] (map) values recurse
+    /// key-by-key: a key/index present on one side only, relative to
+    /// `base`, is an add or a delete and is applied without conflict,
+    /// except that a delete on one side against a change on the other,
+    /// or an add of different values on both sides, is a [Conflict];
+    /// a key present on all three recurses into [Merge3::merge3] on
+    /// its value. [Cbor::Tag] recurses into the tagged value when both
+    /// sides carry the same tag number. Every other case, including a
+    /// structural mismatch between `base`/`local`/`remote`, falls back
+    /// to leaf semantics: take whichever side actually changed, or
+    /// report a [Conflict] when both sides changed to different
+    /// values.
+    fn merge3(base: &Cbor, local: &Cbor, remote: &Cbor) -> result::Result<Cbor, Conflict<Cbor>> {
+        let conflict = || Conflict {
+            base: base.clone(),
+            local: local.clone(),
+            remote: remote.clone(),
+        };
+
+        match (base, local, remote) {
+            (Cbor::Major4(_, b), Cbor::Major4(_, l), Cbor::Major4(_, r)) => {
+                let items = merge3_array(b, l, r).ok_or_else(conflict)?;
+                let n = u64::try_from(items.len()).map_err(|_| conflict())?;
+                Ok(Cbor::Major4(n.into(), items))
+            }
+            (Cbor::Major5(_, b), Cbor::Major5(_, l), Cbor::Major5(_, r)) => {
+                let items = merge3_map(b, l, r).ok_or_else(conflict)?;
+                let n = u64::try_from(items.len()).map_err(|_| conflict())?;
+                Ok(Cbor::Major5(n.into(), items))
+            }
+            (Cbor::Tag(bn, bv), Cbor::Tag(ln, lv), Cbor::Tag(rn, rv)) if bn == ln && bn == rn => {
+                Cbor::merge3(bv, lv, rv)
+                    .map(|val| Cbor::Tag(*ln, Box::new(val)))
+                    .map_err(|_| conflict())
+            }
+            _ if local == base => Ok(remote.clone()),
+            _ if remote == base => Ok(local.clone()),
+            _ if local == remote => Ok(local.clone()),
+            _ => Err(conflict()),
+        }
+    }
+}
+
+// Three-way merge of `(Key, Cbor)` entries, treating a key present on one
+// side only (relative to `base`) as an add or a delete. Returns `None` when
+// some key, present on all three sides, cannot be reconciled, so the caller
+// can report the whole map/array as a single `Conflict`.
+fn merge3_map(
+    base: &[(Key, Cbor)],
+    local: &[(Key, Cbor)],
+    remote: &[(Key, Cbor)],
+) -> Option<Vec<(Key, Cbor)>> {
+    let base: BTreeMap<Key, Cbor> = base.iter().cloned().collect();
+    let local: BTreeMap<Key, Cbor> = local.iter().cloned().collect();
+    let remote: BTreeMap<Key, Cbor> = remote.iter().cloned().collect();
+
+    let mut keys: Vec<Key> = base
+        .keys()
+        .chain(local.keys())
+        .chain(remote.keys())
+        .cloned()
+        .collect();
+    keys.sort();
+    keys.dedup();
+
+    let mut items = Vec::new();
+    for key in keys.into_iter() {
+        if let Some(val) = merge3_optional(base.get(&key), local.get(&key), remote.get(&key))? {
+            items.push((key, val));
+        }
+    }
+    Some(items)
+}
+
+// Three-way merge of a [Cbor::Major4] array, treating element position as
+// the "key". Same add/delete/recurse semantics as `merge3_map`.
+fn merge3_array(base: &[Cbor], local: &[Cbor], remote: &[Cbor]) -> Option<Vec<Cbor>> {
+    let n = base.len().max(local.len()).max(remote.len());
+
+    let mut items = Vec::new();
+    for i in 0..n {
+        if let Some(val) = merge3_optional(base.get(i), local.get(i), remote.get(i))? {
+            items.push(val);
+        }
+    }
+    Some(items)
+}
+
+// Reconcile one base/local/remote triple, any of which may be absent
+// (an add or a delete relative to `base`). Returns `None` when the three
+// sides disagree in a way that cannot be resolved automatically.
+fn merge3_optional(
+    base: Option<&Cbor>,
+    local: Option<&Cbor>,
+    remote: Option<&Cbor>,
+) -> Option<Option<Cbor>> {
+    match (base, local, remote) {
+        (Some(b), Some(l), Some(r)) => Merge3::merge3(b, l, r).ok().map(Some),
+        (Some(b), Some(l), None) => {
+            if l == b {
+                Some(None) // unchanged locally, deleted remotely
+            } else {
+                None // modified locally, deleted remotely
+            }
+        }
+        (Some(b), None, Some(r)) => {
+            if r == b {
+                Some(None) // deleted locally, unchanged remotely
+            } else {
+                None // deleted locally, modified remotely
+            }
+        }
+        (Some(_), None, None) => Some(None), // deleted on both sides
+        (None, Some(l), Some(r)) => {
+            if l == r {
+                Some(Some(l.clone())) // added identically on both sides
+            } else {
+                None // added differently on both sides
+            }
+        }
+        (None, Some(l), None) => Some(Some(l.clone())), // added locally
+        (None, None, Some(r)) => Some(Some(r.clone())), // added remotely
+        (None, None, None) => Some(None),                // unreachable, key wasn't in union
+    }
+}
+
 impl<T> IntoCbor for Option<T>
 where
     T: IntoCbor,
@@ -36,7 +36,6 @@ fn test_simple_value() {
             (Unassigned, Err(_)) => continue,
             (Undefined, Err(_)) => continue,
             (Reserved24(_), Err(_)) => continue,
-            (F16(_), Err(_)) => continue,
             (Break, Err(_)) => continue,
             (_, val) => val.unwrap(),
         };
@@ -74,3 +73,752 @@ fn test_cbor() {
         assert_eq!(val, nval);
     }
 }
+
+#[test]
+fn test_decode_buf() {
+    let seed: u128 = random();
+    println!("test_decode_buf {}", seed);
+    let mut rng = SmallRng::from_seed(seed.to_le_bytes());
+
+    for _i in 0..1000 {
+        let val: Cbor = {
+            let bytes: Vec<u8> = (0..100)
+                .map(|_| rng.gen::<[u8; 32]>().to_vec())
+                .flatten()
+                .collect();
+            let mut uns = Unstructured::new(&bytes);
+            uns.arbitrary().unwrap()
+        };
+
+        let mut buf: Vec<u8> = vec![];
+        let n = val.encode(&mut buf).unwrap();
+
+        // a short, truncated buffer must report "need more data".
+        let mut partial = &buf[..n - 1];
+        assert_eq!(Cbor::decode_buf(&mut partial).unwrap(), None);
+
+        let mut full = buf.as_slice();
+        let (nval, m) = Cbor::decode_buf(&mut full).unwrap().unwrap();
+        assert_eq!(n, m);
+        assert_eq!(val, nval);
+        assert_eq!(full.len(), 0);
+    }
+}
+
+#[test]
+fn test_decode_with_config_rejects_deep_nesting() {
+    // build a chain of `depth` singleton arrays wrapping a plain int,
+    // deeper than a deliberately small `max_nested_depth`.
+    let cfg = DecodeConfig { max_nested_depth: 8, ..DecodeConfig::default() };
+    let depth = cfg.max_nested_depth as usize + 4;
+
+    let mut val = Cbor::Major0(41_u64.into(), 41);
+    for _ in 0..depth {
+        val = Cbor::Major4(1_u64.into(), vec![val]);
+    }
+
+    let mut buf: Vec<u8> = vec![];
+    val.encode(&mut buf).unwrap();
+
+    assert!(Cbor::decode_with_config(&mut buf.as_slice(), &cfg).is_err());
+
+    // plain `decode` keeps its old, more permissive `RECURSION_LIMIT`.
+    assert!(Cbor::decode(&mut buf.as_slice()).is_ok());
+}
+
+#[test]
+fn test_decode_with_config_rejects_nested_tag_depth() {
+    // a chain of tag-39 (Identifier) wrappers exercises `Tag::decode`'s
+    // own recursion -- this used to bypass depth-limiting entirely,
+    // because `Tag::decode` called the public `Cbor::decode`, which
+    // always restarts at depth 1.
+    let cfg = DecodeConfig { max_nested_depth: 8, ..DecodeConfig::default() };
+    let depth = cfg.max_nested_depth as usize + 4;
+
+    let mut val = Cbor::Major0(41_u64.into(), 41);
+    for _ in 0..depth {
+        val = Cbor::Tag(39, Box::new(val));
+    }
+
+    let mut buf: Vec<u8> = vec![];
+    val.encode(&mut buf).unwrap();
+
+    assert!(Cbor::decode_with_config(&mut buf.as_slice(), &cfg).is_err());
+}
+
+#[test]
+fn test_decode_with_config_rejects_oversized_length() {
+    // a byte-string declaring a length past `max_length`, with no
+    // payload bytes following -- must be rejected before the decoder
+    // tries to allocate or read that many bytes.
+    let mut buf: Vec<u8> = vec![0x5b]; // major 2, 8-byte length follows
+    let len = DecodeConfig::default().max_length as u64 + 1;
+    buf.extend_from_slice(&len.to_be_bytes());
+
+    let cfg = DecodeConfig::default();
+    assert!(Cbor::decode_with_config(&mut buf.as_slice(), &cfg).is_err());
+}
+
+#[test]
+fn test_self_described() {
+    let val = 10_u64.into_cbor().unwrap();
+
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode_self_described(&mut buf).unwrap();
+
+    let (nval, m) = Cbor::decode_self_described(&mut buf.as_slice()).unwrap();
+    assert_eq!(n, m);
+    assert_eq!(val, nval);
+
+    // a plain, untagged encoding must be rejected.
+    let mut buf: Vec<u8> = vec![];
+    val.encode(&mut buf).unwrap();
+    assert!(Cbor::decode_self_described(&mut buf.as_slice()).is_err());
+}
+
+#[test]
+fn test_decode_self_described_buf() {
+    let val = 10_u64.into_cbor().unwrap();
+
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode_self_described(&mut buf).unwrap();
+
+    let mut partial = &buf[..n - 1];
+    assert_eq!(Cbor::decode_self_described_buf(&mut partial).unwrap(), None);
+
+    let mut full = buf.as_slice();
+    let (nval, m) = Cbor::decode_self_described_buf(&mut full).unwrap().unwrap();
+    assert_eq!(n, m);
+    assert_eq!(val, nval);
+}
+
+#[test]
+fn test_encode_canonical_sorts_map_keys() {
+    let map = vec![
+        (Key::Text("zz".to_string()), true.into_cbor().unwrap()),
+        (Key::Text("a".to_string()), false.into_cbor().unwrap()),
+    ];
+    let val = Cbor::Major5(Info::Tiny(2), map);
+
+    let mut buf: Vec<u8> = vec![];
+    val.encode_canonical(&mut buf).unwrap();
+
+    let (canon, _) = Cbor::decode_canonical(&mut buf.as_slice()).unwrap();
+    match canon {
+        Cbor::Major5(_, map) => {
+            assert_eq!(map[0].0, Key::Text("a".to_string()));
+            assert_eq!(map[1].0, Key::Text("zz".to_string()));
+        }
+        val => panic!("unexpected {:?}", val),
+    }
+}
+
+#[test]
+fn test_encode_canonical_sorts_by_key_bytes_not_key_ord() {
+    // `Key::Ord` groups `N64`/`U64` under the same type-order bucket and
+    // always ranks a negative integer before a non-negative one -- see
+    // `Key::to_type_order`/`Key::cmp`. Canonical encoding must ignore
+    // that semantic ordering and sort by each key's own encoded bytes
+    // instead: `U64(5)` encodes as the single byte `0x05`, `N64(-1)` as
+    // `0x20`, so canonically `U64(5)` sorts first even though
+    // `Key::Ord` says the opposite.
+    assert!(Key::N64(-1) < Key::U64(5));
+
+    let map = vec![
+        (Key::N64(-1), true.into_cbor().unwrap()),
+        (Key::U64(5), false.into_cbor().unwrap()),
+    ];
+    let val = Cbor::Major5(Info::Tiny(2), map);
+
+    let mut buf: Vec<u8> = vec![];
+    val.encode_canonical(&mut buf).unwrap();
+
+    let (canon, _) = Cbor::decode_canonical(&mut buf.as_slice()).unwrap();
+    match canon {
+        Cbor::Major5(_, map) => {
+            assert_eq!(map[0].0, Key::U64(5));
+            assert_eq!(map[1].0, Key::N64(-1));
+        }
+        val => panic!("unexpected {:?}", val),
+    }
+}
+
+#[test]
+fn test_encode_canonical_narrows_floats() {
+    // exact in f32 (needs 16 mantissa bits), but not narrow enough for
+    // f16's 10, so this should stop at f32.
+    let exact: f64 = 1.0 + 2_f64.powi(-16);
+    let val = exact.into_cbor().unwrap();
+
+    let mut buf: Vec<u8> = vec![];
+    val.encode_canonical(&mut buf).unwrap();
+
+    let (canon, _) = Cbor::decode_canonical(&mut buf.as_slice()).unwrap();
+    match canon {
+        Cbor::Major7(_, SimpleValue::F32(f)) => assert_eq!(f64::from(f), exact),
+        val => panic!("expected narrowed f32, found {:?}", val),
+    }
+}
+
+#[test]
+fn test_encode_canonical_narrows_nan() {
+    // every NaN, regardless of payload, must canonicalize to f16's
+    // canonical quiet-NaN so that all equal (NaN) values produce
+    // identical canonical bytes.
+    let val = f64::NAN.into_cbor().unwrap();
+
+    let mut buf: Vec<u8> = vec![];
+    val.encode_canonical(&mut buf).unwrap();
+
+    let (canon, _) = Cbor::decode_canonical(&mut buf.as_slice()).unwrap();
+    match canon {
+        Cbor::Major7(_, SimpleValue::F16(bits)) => assert_eq!(bits, 0x7e00),
+        val => panic!("expected narrowed f16 nan, found {:?}", val),
+    }
+}
+
+#[test]
+fn test_decode_canonical_rejects_non_canonical() {
+    // `5` encoded the long way, in a trailing byte, instead of inline
+    // as `Info::Tiny(5)`.
+    let buf = vec![0x18, 5];
+    assert!(Cbor::decode_canonical(&mut buf.as_slice()).is_err());
+}
+
+#[test]
+fn test_f16_roundtrip() {
+    // zero, a normal value, and the smallest/largest subnormals.
+    for bits in [0x0000_u16, 0x3c00, 0x0001, 0x03ff, 0x7bff, 0x8000, 0xbc00] {
+        let f32_val = SimpleValue::f16_to_f32(bits);
+        assert_eq!(SimpleValue::f32_to_f16(f32_val), bits, "bits {:#06x}", bits);
+
+        let val = Cbor::Major7(Info::U16, SimpleValue::F16(bits));
+        let mut buf: Vec<u8> = vec![];
+        let n = val.encode(&mut buf).unwrap();
+        let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(n, m);
+        assert_eq!(val, nval);
+    }
+
+    // infinity and NaN widen without losing their class.
+    assert!(SimpleValue::f16_to_f32(0x7c00).is_infinite());
+    assert!(SimpleValue::f16_to_f32(0x7e00).is_nan());
+}
+
+#[test]
+fn test_from_f64_smallest() {
+    match SimpleValue::from_f64_smallest(1.5) {
+        SimpleValue::F16(_) => (),
+        val => panic!("expected f16, found {:?}", val),
+    }
+
+    match SimpleValue::from_f64_smallest(1.5e300) {
+        SimpleValue::F64(_) => (),
+        val => panic!("expected f64, found {:?}", val),
+    }
+
+    match SimpleValue::from_f64_smallest(1.0 / 3.0) {
+        SimpleValue::F64(_) => (),
+        val => panic!("expected f64, found {:?}", val),
+    }
+
+    // NaN always narrows to f16's canonical quiet-NaN, regardless of
+    // sign or payload bits, since `NaN == NaN` can never guide the
+    // round-trip narrowing used for finite values.
+    match SimpleValue::from_f64_smallest(f64::NAN) {
+        SimpleValue::F16(bits) => assert_eq!(bits, 0x7e00),
+        val => panic!("expected f16 nan, found {:?}", val),
+    }
+    match SimpleValue::from_f64_smallest(-f64::NAN) {
+        SimpleValue::F16(bits) => assert_eq!(bits, 0x7e00),
+        val => panic!("expected f16 nan, found {:?}", val),
+    }
+}
+
+#[test]
+fn test_decode_slice_borrows_bytes_and_text() {
+    let val = Cbor::bytes_into_cbor(b"hello".to_vec()).unwrap();
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode(&mut buf).unwrap();
+
+    let (cref, m) = Cbor::decode_slice(&buf).unwrap();
+    assert_eq!(n, m);
+    match &cref {
+        CborRef::Major2(_, byts) => {
+            assert_eq!(&**byts, b"hello".as_slice());
+            // it's a borrow of `buf`, not a fresh allocation.
+            assert_eq!(byts.as_ptr(), buf[buf.len() - 5..].as_ptr());
+        }
+        val => panic!("unexpected {:?}", val),
+    }
+    assert_eq!(cref.into_owned(), val);
+
+    let val = "world".to_string().into_cbor().unwrap();
+    let mut buf: Vec<u8> = vec![];
+    val.encode(&mut buf).unwrap();
+
+    let (cref, _) = Cbor::decode_slice(&buf).unwrap();
+    match &cref {
+        CborRef::Major3(_, text) => assert_eq!(&**text, "world"),
+        val => panic!("unexpected {:?}", val),
+    }
+    assert_eq!(cref.into_owned(), val);
+}
+
+#[test]
+fn test_decode_slice_stream_offset() {
+    let val1 = 10_u64.into_cbor().unwrap();
+    let val2 = "two".to_string().into_cbor().unwrap();
+
+    let mut stream: Vec<u8> = vec![];
+    val1.encode(&mut stream).unwrap();
+    val2.encode(&mut stream).unwrap();
+
+    let (c1, n1) = Cbor::decode_slice(&stream).unwrap();
+    assert_eq!(c1.into_owned(), val1);
+
+    let (c2, n2) = Cbor::decode_slice(&stream[n1..]).unwrap();
+    assert_eq!(c2.into_owned(), val2);
+    assert_eq!(n1 + n2, stream.len());
+}
+
+#[test]
+fn test_encode_canonical_narrows_to_f16() {
+    let val = 1.5_f64.into_cbor().unwrap();
+
+    let mut buf: Vec<u8> = vec![];
+    val.encode_canonical(&mut buf).unwrap();
+
+    let (canon, _) = Cbor::decode_canonical(&mut buf.as_slice()).unwrap();
+    match canon {
+        Cbor::Major7(_, SimpleValue::F16(bits)) => {
+            assert_eq!(SimpleValue::f16_to_f32(bits), 1.5);
+        }
+        val => panic!("expected narrowed f16, found {:?}", val),
+    }
+}
+
+#[test]
+fn test_tag_date_time_roundtrip() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    for time in [
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        UNIX_EPOCH - Duration::from_secs(86_400 * 400),
+        UNIX_EPOCH,
+    ] {
+        let val: Cbor = Tag::DateTime(time).into();
+
+        let mut buf: Vec<u8> = vec![];
+        let n = val.encode(&mut buf).unwrap();
+        let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(n, m);
+        match nval {
+            Cbor::Major6(_, Tag::DateTime(got)) => assert_eq!(got, time),
+            val => panic!("unexpected {:?}", val),
+        }
+    }
+}
+
+#[test]
+fn test_tag_epoch_time_roundtrip() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let time = UNIX_EPOCH - Duration::from_secs(3600);
+    let val = time.into_cbor().unwrap();
+
+    let mut buf: Vec<u8> = vec![];
+    val.encode(&mut buf).unwrap();
+    let (nval, _) = Cbor::decode(&mut buf.as_slice()).unwrap();
+    assert_eq!(SystemTime::from_cbor(nval).unwrap(), time);
+
+    // tag-1 is decoded leniently from a plain integer too.
+    let mut buf: Vec<u8> = vec![];
+    10_u64.into_cbor().unwrap().encode(&mut buf).unwrap();
+    let mut tagged = vec![0xc1];
+    tagged.extend(buf);
+    let (nval, n) = Cbor::decode(&mut tagged.as_slice()).unwrap();
+    assert_eq!(n, tagged.len());
+    match nval {
+        Cbor::Major6(_, Tag::EpochTime(got)) => {
+            assert_eq!(got, UNIX_EPOCH + Duration::from_secs(10));
+        }
+        val => panic!("unexpected {:?}", val),
+    }
+}
+
+#[test]
+fn test_bignum_roundtrip() {
+    for val in [
+        0_i128,
+        1,
+        -1,
+        i128::MAX,
+        i128::MIN,
+        1_000_000_000_000_000_000_000_i128,
+        -1_000_000_000_000_000_000_000_i128,
+    ] {
+        let big = BigInt::from_i128(val);
+        assert_eq!(big.to_i128().unwrap(), val, "value {}", val);
+
+        let cbor_val = big.clone().into_cbor().unwrap();
+
+        let mut buf: Vec<u8> = vec![];
+        let n = cbor_val.encode(&mut buf).unwrap();
+        let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+        assert_eq!(n, m);
+        assert_eq!(BigInt::from_cbor(nval).unwrap(), big);
+    }
+}
+
+#[test]
+fn test_expected_encoding_preserves_nested_value() {
+    let inner: Cbor = 42_u64.into_cbor().unwrap();
+    let val: Cbor = Tag::ExpectedEncoding(Encoding::Base64, Box::new(inner.clone())).into();
+
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode(&mut buf).unwrap();
+    let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+    assert_eq!(n, m);
+    match nval {
+        Cbor::Major6(_, Tag::ExpectedEncoding(Encoding::Base64, got)) => {
+            assert_eq!(*got, inner);
+        }
+        val => panic!("unexpected {:?}", val),
+    }
+}
+
+#[test]
+fn test_tag_link_roundtrip() {
+    let cid = Cid::new(vec![1, 85, 18, 32, 7, 7, 7]);
+    let val: Cbor = Tag::Link(cid.clone()).into();
+
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode(&mut buf).unwrap();
+    let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+    assert_eq!(n, m);
+    assert_eq!(val, nval);
+
+    // the wire form must be tag-42 wrapping a byte-string starting with
+    // the multibase-identity byte.
+    assert_eq!(&buf[..4], &[0xd8, 42, 0x48, 0x00]);
+    match &nval {
+        Cbor::Major6(_, Tag::Link(got)) => assert_eq!(got, &cid),
+        val => panic!("unexpected {:?}", val),
+    }
+
+    assert_eq!(Cid::from_cbor(nval).unwrap(), cid);
+}
+
+#[test]
+fn test_tag_link_missing_multibase_prefix_errors() {
+    // tag-42 wrapping a byte-string whose first byte isn't the
+    // multibase-identity `0x00`.
+    let tagged = Cbor::Tag(42, Box::new(Cbor::bytes_into_cbor(vec![1, 2]).unwrap()));
+
+    let mut buf: Vec<u8> = vec![];
+    tagged.encode(&mut buf).unwrap();
+
+    assert!(Cbor::decode(&mut buf.as_slice()).is_err());
+}
+
+#[test]
+fn test_tag_unknown_value_roundtrip() {
+    let val: Cbor = Tag::Value(1000).into();
+
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode(&mut buf).unwrap();
+    let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+    assert_eq!(n, m);
+    assert_eq!(val, nval);
+}
+
+#[test]
+fn test_to_diag() {
+    let map = vec![(Key::Text("a".to_string()), Cbor::bytes_into_cbor(vec![1, 2]).unwrap())];
+    let val = Cbor::Major4(
+        Info::Tiny(4),
+        vec![
+            1_u64.into_cbor().unwrap(),
+            2_u64.into_cbor().unwrap(),
+            Cbor::Major5(Info::Tiny(1), map),
+            3.14_f64.into_cbor().unwrap(),
+            true.into_cbor().unwrap(),
+            SimpleValue::Null.into_cbor().unwrap(),
+        ],
+    );
+
+    assert_eq!(
+        val.to_diag().unwrap(),
+        r#"[1, 2, {"a": h'0102'}, 3.14, true, null]"#
+    );
+}
+
+#[test]
+fn test_to_diag_indefinite_and_tags() {
+    let indef = Cbor::Major2(Info::Indefinite, vec![9]);
+    assert_eq!(indef.to_diag().unwrap(), "_ h'09'");
+
+    let tagged = Cbor::Tag(100, Box::new(5_u64.into_cbor().unwrap()));
+    assert_eq!(tagged.to_diag().unwrap(), "100(5)");
+
+    let dt: Cbor = Tag::DateTime(std::time::UNIX_EPOCH).into();
+    assert_eq!(dt.to_diag().unwrap(), r#"0("1970-01-01T00:00:00Z")"#);
+}
+
+#[test]
+fn test_indefinite_bytes_roundtrip() {
+    let val = Cbor::Major2(Info::Indefinite, vec![1, 2, 3]);
+
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode(&mut buf).unwrap();
+    // 0x5f (indefinite byte-string) + 0x43 0x01 0x02 0x03 (one chunk) + 0xff (break)
+    assert_eq!(buf, vec![0x5f, 0x43, 1, 2, 3, 0xff]);
+
+    let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+    assert_eq!(n, m);
+    assert_eq!(nval, val);
+}
+
+#[test]
+fn test_indefinite_text_roundtrip() {
+    let val = Cbor::Major3(Info::Indefinite, "hi".as_bytes().to_vec());
+
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode(&mut buf).unwrap();
+
+    let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+    assert_eq!(n, m);
+    assert_eq!(nval, val);
+}
+
+#[test]
+fn test_indefinite_array_roundtrip() {
+    let list = vec![1_u64.into_cbor().unwrap(), 2_u64.into_cbor().unwrap()];
+    let val = Cbor::Major4(Info::Indefinite, list);
+
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode(&mut buf).unwrap();
+    assert_eq!(buf[0], 0x9f); // major4, indefinite
+    assert_eq!(*buf.last().unwrap(), 0xff); // break
+
+    let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+    assert_eq!(n, m);
+    assert_eq!(nval, val);
+}
+
+#[test]
+fn test_indefinite_map_roundtrip() {
+    let map = vec![(Key::from_cbor(1_u64.into_cbor().unwrap()).unwrap(), 2_u64.into_cbor().unwrap())];
+    let val = Cbor::Major5(Info::Indefinite, map);
+
+    let mut buf: Vec<u8> = vec![];
+    let n = val.encode(&mut buf).unwrap();
+    assert_eq!(buf[0], 0xbf); // major5, indefinite
+    assert_eq!(*buf.last().unwrap(), 0xff); // break
+
+    let (nval, m) = Cbor::decode(&mut buf.as_slice()).unwrap();
+    assert_eq!(n, m);
+    assert_eq!(nval, val);
+}
+
+#[test]
+fn test_decoder_byte_at_a_time() {
+    let seed: u128 = random();
+    println!("test_decoder_byte_at_a_time {}", seed);
+    let mut rng = SmallRng::from_seed(seed.to_le_bytes());
+
+    for _i in 0..1000 {
+        let val: Cbor = {
+            let bytes: Vec<u8> = (0..100)
+                .map(|_| rng.gen::<[u8; 32]>().to_vec())
+                .flatten()
+                .collect();
+            let mut uns = Unstructured::new(&bytes);
+            uns.arbitrary().unwrap()
+        };
+
+        let mut buf: Vec<u8> = vec![];
+        let n = val.encode(&mut buf).unwrap();
+
+        let mut decoder = Decoder::new();
+        let mut ready = None;
+        for (i, byte) in buf.iter().enumerate() {
+            match decoder.feed(&[*byte]).unwrap() {
+                Poll::Pending => assert!(i + 1 < n, "pending past end of value"),
+                Poll::Ready((nval, m)) => {
+                    ready = Some((nval, m));
+                    break;
+                }
+            }
+        }
+        let (nval, m) = ready.unwrap();
+        assert_eq!(n, m);
+        assert_eq!(val, nval);
+    }
+}
+
+#[test]
+fn test_decoder_drains_consumed_and_resumes() {
+    let val1: Cbor = 42_u64.into_cbor().unwrap();
+    let val2: Cbor = "hello".to_string().into_cbor().unwrap();
+
+    let mut buf: Vec<u8> = vec![];
+    val1.encode(&mut buf).unwrap();
+    val2.encode(&mut buf).unwrap();
+
+    let mut decoder = Decoder::new();
+    match decoder.feed(&buf).unwrap() {
+        Poll::Ready((nval, _)) => assert_eq!(nval, val1),
+        Poll::Pending => panic!("expected first value to be ready"),
+    }
+    match decoder.feed(&[]).unwrap() {
+        Poll::Ready((nval, _)) => assert_eq!(nval, val2),
+        Poll::Pending => panic!("expected second value to be ready"),
+    }
+}
+
+#[test]
+fn test_merge3_identity() {
+    let base = 10_u64.into_cbor().unwrap();
+    let leaf = "hello".to_string().into_cbor().unwrap();
+    let map = Cbor::Major5(
+        Info::Tiny(2),
+        vec![
+            (Key::Text("a".to_string()), 1_u64.into_cbor().unwrap()),
+            (Key::Text("b".to_string()), 2_u64.into_cbor().unwrap()),
+        ],
+    );
+    let arr = Cbor::Major4(
+        Info::Tiny(2),
+        vec![1_u64.into_cbor().unwrap(), 2_u64.into_cbor().unwrap()],
+    );
+
+    for val in [leaf, map, arr] {
+        assert_eq!(Cbor::merge3(&base, &val, &val).unwrap(), val);
+    }
+}
+
+#[test]
+fn test_merge3_commutative() {
+    let base = 1_u64.into_cbor().unwrap();
+    let local = 2_u64.into_cbor().unwrap();
+    let remote = 3_u64.into_cbor().unwrap();
+
+    // conflicting leaf: order of `local`/`remote` must not matter.
+    let forward = Cbor::merge3(&base, &local, &remote);
+    let backward = Cbor::merge3(&base, &remote, &local);
+    assert!(forward.is_err());
+    assert!(backward.is_err());
+
+    // only one side changed: result is the same regardless of which
+    // argument carried the change.
+    assert_eq!(
+        Cbor::merge3(&base, &local, &base).unwrap(),
+        Cbor::merge3(&base, &base, &local).unwrap(),
+    );
+}
+
+#[test]
+fn test_merge3_conflict_on_diverging_leaf() {
+    let base = 1_u64.into_cbor().unwrap();
+    let local = 2_u64.into_cbor().unwrap();
+    let remote = 3_u64.into_cbor().unwrap();
+
+    let err = Cbor::merge3(&base, &local, &remote).unwrap_err();
+    assert_eq!(err.base, base);
+    assert_eq!(err.local, local);
+    assert_eq!(err.remote, remote);
+}
+
+#[test]
+fn test_merge3_map_add_delete_and_recurse() {
+    let base = Cbor::Major5(
+        Info::Tiny(2),
+        vec![
+            (Key::Text("kept".to_string()), 1_u64.into_cbor().unwrap()),
+            (Key::Text("removed".to_string()), 2_u64.into_cbor().unwrap()),
+        ],
+    );
+    // `local` deletes "removed" and changes "kept"; `remote` leaves
+    // "removed" untouched and adds "added".
+    let local = Cbor::Major5(
+        Info::Tiny(1),
+        vec![(Key::Text("kept".to_string()), 10_u64.into_cbor().unwrap())],
+    );
+    let remote = Cbor::Major5(
+        Info::Tiny(3),
+        vec![
+            (Key::Text("added".to_string()), 4_u64.into_cbor().unwrap()),
+            (Key::Text("kept".to_string()), 1_u64.into_cbor().unwrap()),
+            (
+                Key::Text("removed".to_string()),
+                2_u64.into_cbor().unwrap(),
+            ),
+        ],
+    );
+
+    let merged = Cbor::merge3(&base, &local, &remote).unwrap();
+    match merged {
+        Cbor::Major5(_, map) => {
+            let map: std::collections::BTreeMap<_, _> = map.into_iter().collect();
+            assert_eq!(map.len(), 2);
+            assert_eq!(map[&Key::Text("kept".to_string())], 10_u64.into_cbor().unwrap());
+            assert_eq!(map[&Key::Text("added".to_string())], 4_u64.into_cbor().unwrap());
+            assert!(!map.contains_key(&Key::Text("removed".to_string())));
+        }
+        val => panic!("expected a map, got {:?}", val),
+    }
+}
+
+#[test]
+fn test_merge3_map_add_delete_round_trips_through_encode() {
+    // `local`'s `Info` (Tiny(1)) does not match the merged item count
+    // (2): the merge must recompute `Info` from the merged length,
+    // not carry `local`'s stale header, or the encoded bytes would
+    // declare the wrong length and desync a decoder.
+    let base = Cbor::Major5(
+        Info::Tiny(2),
+        vec![
+            (Key::Text("kept".to_string()), 1_u64.into_cbor().unwrap()),
+            (Key::Text("removed".to_string()), 2_u64.into_cbor().unwrap()),
+        ],
+    );
+    let local = Cbor::Major5(
+        Info::Tiny(1),
+        vec![(Key::Text("kept".to_string()), 1_u64.into_cbor().unwrap())],
+    );
+    let remote = Cbor::Major5(
+        Info::Tiny(3),
+        vec![
+            (Key::Text("added".to_string()), 4_u64.into_cbor().unwrap()),
+            (Key::Text("kept".to_string()), 1_u64.into_cbor().unwrap()),
+            (
+                Key::Text("removed".to_string()),
+                2_u64.into_cbor().unwrap(),
+            ),
+        ],
+    );
+
+    let merged = Cbor::merge3(&base, &local, &remote).unwrap();
+
+    let mut buf: Vec<u8> = vec![];
+    merged.clone().encode(&mut buf).unwrap();
+    let (decoded, n) = Cbor::decode(&mut buf.as_slice()).unwrap();
+    assert_eq!(n, buf.len());
+    assert_eq!(decoded, merged);
+
+    match decoded {
+        Cbor::Major5(_, map) => assert_eq!(map.len(), 2),
+        val => panic!("expected a map, got {:?}", val),
+    }
+}
+
+#[test]
+fn test_merge3_array_conflicting_element() {
+    let base = Cbor::Major4(Info::Tiny(1), vec![1_u64.into_cbor().unwrap()]);
+    let local = Cbor::Major4(Info::Tiny(1), vec![2_u64.into_cbor().unwrap()]);
+    let remote = Cbor::Major4(Info::Tiny(1), vec![3_u64.into_cbor().unwrap()]);
+
+    assert!(Cbor::merge3(&base, &local, &remote).is_err());
+}
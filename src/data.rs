@@ -1,5 +1,7 @@
 //! Module define all things data related.
 
+use std::result;
+
 use crate::LocalCborize;
 
 const NDIFF_VER: u32 = 0x0001;
@@ -33,6 +35,65 @@ pub trait Diff: Sized + From<<Self as Diff>::Delta> {
     fn merge(&self, delta: &Self::Delta) -> Self;
 }
 
+/// Trait for three-way merging of divergent, concurrently-written values.
+///
+/// [Diff] only models centralized version control: a single lineage of
+/// successive versions. Distributed and concurrent writers instead produce
+/// two divergent descendants, `local` and `remote`, of a common ancestor
+/// `base`, and need a way to reconcile them without a central arbiter.
+///
+/// The default policy, used by the primitive-type impls below, is:
+/// * If only one side changed relative to `base`, take the changed side.
+/// * If both sides changed to the same value, take that value.
+/// * If both sides changed to different values, return [Conflict] so the
+///   caller can apply its own resolution policy.
+pub trait Merge3: Diff {
+    /// Reconcile `local` and `remote`, both descendants of `base`.
+    fn merge3(
+        base: &Self,
+        local: &Self,
+        remote: &Self,
+    ) -> result::Result<Self, Conflict<Self>>;
+}
+
+/// Carries both divergent candidates, plus their common ancestor, when
+/// [Merge3::merge3] cannot resolve a three-way merge on its own.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Conflict<T> {
+    pub base: T,
+    pub local: T,
+    pub remote: T,
+}
+
+macro_rules! impl_merge3_basic_types {
+    ($($type:ident,)*) => (
+        $(
+            impl Merge3 for $type {
+                fn merge3(
+                    base: &Self,
+                    local: &Self,
+                    remote: &Self,
+                ) -> result::Result<Self, Conflict<Self>> {
+                    match (local == base, remote == base, local == remote) {
+                        (true, _, _) => Ok(*remote),
+                        (_, true, _) => Ok(*local),
+                        (_, _, true) => Ok(*local),
+                        (false, false, false) => Err(Conflict {
+                            base: *base,
+                            local: *local,
+                            remote: *remote,
+                        }),
+                    }
+                }
+            }
+        )*
+    );
+}
+
+impl_merge3_basic_types![
+    bool, char, f32, f64, i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize,
+];
+
 /// Associated type for value-type that don't implement [Diff] trait, i.e
 /// whereever applicable, use NoDiff as delta type.
 #[derive(Clone, Default, Debug, LocalCborize)]
@@ -1,10 +1,21 @@
 //! Module define all things database related.
 
-use std::{borrow::Borrow, fmt, hash::Hash, ops::Bound};
+use std::{
+    borrow::Borrow,
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    convert::TryFrom,
+    fmt,
+    hash::Hash,
+    iter::Peekable,
+    ops::Bound,
+};
+
+use crate::cbor::{Cbor, FromCbor, IntoCbor};
 
 #[allow(unused_imports)]
 use crate::data::{Diff, NoDiff};
-use crate::{Error, LocalCborize};
+use crate::{Error, LocalCborize, Result};
 
 /// Trait to bulk-add entries into an index.
 pub trait BuildIndex<K, V, D, B> {
@@ -50,6 +61,16 @@ pub trait Bloom: Sized + Default {
     /// Deserialize the binary array to bit-map.
     fn from_bytes(buf: &[u8]) -> Result<(Self, usize), Self::Err>;
 
+    /// Incremental counterpart of [Bloom::from_bytes], driven by an
+    /// abstract [crate::cbor::Buf] cursor instead of a complete,
+    /// contiguous byte slice. Returns `Ok(None)` when `buf` does not
+    /// yet hold a complete bit-map, leaving `buf` untouched so the
+    /// caller can feed it more bytes, e.g. from a socket or a chunked
+    /// file-read, and retry.
+    fn from_buf<B>(buf: &mut B) -> Result<Option<(Self, usize)>, Self::Err>
+    where
+        B: crate::cbor::Buf;
+
     /// Merge two bitmaps.
     fn or(&self, other: &Self) -> Result<Self, Self::Err>;
 }
@@ -286,6 +307,57 @@ impl<K, V, D> Entry<K, V, D> {
         values
     }
 
+    /// Reconstruct the value visible to a reader reading as of `seqno`,
+    /// e.g. for a snapshot/MVCC read. Returns `None` if the entry's value
+    /// as of `seqno` is a tombstone, or if `seqno` predates the entry's
+    /// oldest recorded version.
+    ///
+    /// Walks the descending-seqno `deltas` from the newest value down,
+    /// stopping at the first version whose seqno is `<= seqno`, so cost
+    /// is O(number of versions newer than `seqno`) rather than the
+    /// O(all versions) of rebuilding the full [Entry::to_values].
+    ///
+    /// Requires `V: Diff<Delta = D> + Clone`.
+    pub fn get_as_of(&self, seqno: u64) -> Option<V>
+    where
+        V: Diff<Delta = D> + Clone,
+        D: Clone,
+    {
+        if self.to_seqno() <= seqno {
+            return self.to_value();
+        }
+
+        let mut val = self.to_value();
+        for d in self.deltas.iter() {
+            val = match (val, d.clone()) {
+                (Some(v), Delta::U { delta, .. }) => Some(v.merge(&delta)),
+                (Some(_), Delta::D { .. }) => None,
+                (None, Delta::U { delta, .. }) => Some(delta.into()),
+                (None, Delta::D { .. }) => None,
+            };
+            if d.to_seqno() <= seqno {
+                return val;
+            }
+        }
+
+        None
+    }
+
+    /// Companion to [Entry::get_as_of]: the seqno of the version visible
+    /// as of `seqno`, whether or not that version is a tombstone. `None`
+    /// if `seqno` predates the entry's oldest recorded version.
+    pub fn to_seqno_as_of(&self, seqno: u64) -> Option<u64> {
+        let head_seqno = self.to_seqno();
+        if head_seqno <= seqno {
+            return Some(head_seqno);
+        }
+
+        self.deltas
+            .iter()
+            .map(Delta::to_seqno)
+            .find(|seqno_d| *seqno_d <= seqno)
+    }
+
     pub fn contains(&self, other: &Self) -> bool
     where
         V: Clone + PartialEq + Diff<Delta = D>,
@@ -403,6 +475,341 @@ pub enum Cutoff {
     Tombstone(Bound<u64>),
 }
 
+/// Merge `N` ascending, individually-versioned [Entry] iterators into a
+/// single ascending, version-merged stream, e.g. to fold several sorted
+/// LSM snapshots into one before handing the result to
+/// [BuildIndex::build_index].
+///
+/// Every source must yield entries in ascending key order, with seqnos
+/// that are globally comparable across sources. A binary min-heap, keyed
+/// on each source's peeked [Entry::as_key], picks the smallest pending
+/// key on every step; every source currently fronting that key is popped
+/// and folded left-to-right, in ascending source order, through
+/// [Entry::merge], so that when two sources carry the same key at the
+/// same seqno the later source's entry is the one applied last. An
+/// optional [Cutoff] is then applied to the merged entry via
+/// [Entry::purge], and the entry is dropped when that returns `None`.
+pub struct Merge<K, V, D> {
+    sources: Vec<Peekable<Box<dyn Iterator<Item = Entry<K, V, D>>>>>,
+    heap: BinaryHeap<Reverse<(K, usize)>>,
+    cutoff: Option<Cutoff>,
+}
+
+impl<K, V, D> Merge<K, V, D>
+where
+    K: Ord + Clone,
+{
+    pub fn new(
+        sources: Vec<Box<dyn Iterator<Item = Entry<K, V, D>>>>,
+        cutoff: Option<Cutoff>,
+    ) -> Self {
+        let mut sources: Vec<_> = sources.into_iter().map(|src| src.peekable()).collect();
+
+        let mut heap = BinaryHeap::with_capacity(sources.len());
+        for (i, src) in sources.iter_mut().enumerate() {
+            if let Some(entry) = src.peek() {
+                heap.push(Reverse((entry.as_key().clone(), i)));
+            }
+        }
+
+        Merge { sources, heap, cutoff }
+    }
+}
+
+impl<K, V, D> Iterator for Merge<K, V, D>
+where
+    K: Ord + Clone,
+    V: Clone + Diff<Delta = D>,
+    D: Clone + From<V>,
+{
+    type Item = Entry<K, V, D>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let Reverse((min_key, _)) = self.heap.peek()?.clone();
+
+            let mut merged: Option<Entry<K, V, D>> = None;
+            while let Some(&Reverse((ref key, _))) = self.heap.peek() {
+                if *key != min_key {
+                    break;
+                }
+                let Reverse((_, i)) = self.heap.pop().unwrap();
+
+                let entry = self.sources[i]
+                    .next()
+                    .expect("heap entry implies a peeked front exists");
+                merged = Some(match merged {
+                    None => entry,
+                    Some(acc) => acc.merge(&entry),
+                });
+
+                if let Some(next_entry) = self.sources[i].peek() {
+                    self.heap.push(Reverse((next_entry.as_key().clone(), i)));
+                }
+            }
+
+            let merged = merged?;
+            match self.cutoff {
+                Some(cutoff) => {
+                    if let Some(entry) = merged.purge(cutoff) {
+                        return Some(entry);
+                    }
+                    // Entry was purged outright, keep scanning for the next key.
+                }
+                None => return Some(merged),
+            }
+        }
+    }
+}
+
+// A single, not-yet-sequenced mutation recorded in a [WriteBatch].
+#[derive(Clone)]
+enum Op<K, V> {
+    Set { key: K, value: V },
+    Delete { key: K },
+}
+
+/// An ordered, atomic batch of `{key,value}` mutations, in the style of
+/// LevelDB's write batch.
+///
+/// Record mutations with [WriteBatch::set] and [WriteBatch::delete], then
+/// hand the batch to [WriteBatch::into_entries] with a starting seqno:
+/// operations are grouped by key, assigned consecutive seqnos in
+/// insertion order, and repeated mutations of the same key are folded
+/// through [Entry::insert]/[Entry::delete] so the resulting entries carry
+/// correct delta chains, ready to feed straight into
+/// [BuildIndex::build_index].
+pub struct WriteBatch<K, V, D = NoDiff> {
+    ops: Vec<Op<K, V>>,
+    _delta: std::marker::PhantomData<D>,
+}
+
+impl<K, V, D> Default for WriteBatch<K, V, D> {
+    fn default() -> Self {
+        WriteBatch::new()
+    }
+}
+
+impl<K, V, D> WriteBatch<K, V, D> {
+    pub fn new() -> Self {
+        WriteBatch {
+            ops: Vec::default(),
+            _delta: std::marker::PhantomData,
+        }
+    }
+
+    /// Record a `{key,value}` mutation.
+    pub fn set(&mut self, key: K, value: V) {
+        self.ops.push(Op::Set { key, value });
+    }
+
+    /// Record a deletion of `key`.
+    pub fn delete(&mut self, key: K) {
+        self.ops.push(Op::Delete { key });
+    }
+
+    /// Number of mutations recorded so far.
+    pub fn count(&self) -> usize {
+        self.ops.len()
+    }
+
+    /// Approximate on-the-wire size, in bytes, of the mutations recorded
+    /// so far, computed by CBOR-encoding each one.
+    pub fn byte_size(&self) -> Result<usize>
+    where
+        K: Clone + IntoCbor,
+        V: Clone + IntoCbor,
+    {
+        let mut n = 0;
+        for op in self.ops.iter() {
+            let cbor = match op.clone() {
+                Op::Set { key, value } => vec![key.into_cbor()?, value.into_cbor()?].into_cbor()?,
+                Op::Delete { key } => key.into_cbor()?,
+            };
+            let mut buf: Vec<u8> = vec![];
+            n += cbor.encode(&mut buf)?;
+        }
+        Ok(n)
+    }
+
+    /// Materialize the recorded mutations into [Entry] values, assigning
+    /// consecutive seqnos starting at `base_seqno` in insertion order.
+    /// Returns the entries, one per distinct key in first-touched order,
+    /// alongside the next free seqno.
+    pub fn into_entries(self, base_seqno: u64) -> (Vec<Entry<K, V, D>>, u64)
+    where
+        K: Eq + Hash + Clone,
+        V: Clone + Diff<Delta = D>,
+        D: Clone + From<V>,
+    {
+        let mut order: Vec<K> = Vec::new();
+        let mut entries: HashMap<K, Entry<K, V, D>> = HashMap::new();
+        let mut seqno = base_seqno;
+
+        for op in self.ops.into_iter() {
+            let this_seqno = seqno;
+            seqno += 1;
+
+            let key = match &op {
+                Op::Set { key, .. } => key.clone(),
+                Op::Delete { key } => key.clone(),
+            };
+
+            match entries.get_mut(&key) {
+                Some(entry) => match op {
+                    Op::Set { value, .. } => entry.insert(value, this_seqno),
+                    Op::Delete { .. } => entry.delete(this_seqno),
+                },
+                None => {
+                    let entry = match op {
+                        Op::Set { key, value } => Entry::new(key, value, this_seqno),
+                        Op::Delete { key } => Entry::new_deleted(key, this_seqno),
+                    };
+                    order.push(key.clone());
+                    entries.insert(key, entry);
+                }
+            }
+        }
+
+        let entries: Vec<Entry<K, V, D>> =
+            order.into_iter().map(|key| entries.remove(&key).unwrap()).collect();
+        (entries, seqno)
+    }
+}
+
+// Bytes occupied by `encode_block`'s fixed framing: a u32 body length, a
+// u8 codec tag, and a trailing u32 crc32 of the uncompressed payload.
+const BLOCK_HEADER_LEN: usize = 4 + 1;
+const BLOCK_TRAILER_LEN: usize = 4;
+
+/// Compression codec framed into an [encode_block] block.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum BlockCodec {
+    /// Store the CBOR payload as-is.
+    None = 0,
+    Lz4 = 1,
+    Snappy = 2,
+}
+
+impl TryFrom<u8> for BlockCodec {
+    type Error = Error;
+
+    fn try_from(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(BlockCodec::None),
+            1 => Ok(BlockCodec::Lz4),
+            2 => Ok(BlockCodec::Snappy),
+            tag => err_at!(FailConvert, msg: "unknown block codec tag {}", tag),
+        }
+    }
+}
+
+// IEEE CRC-32 (poly 0xEDB88320, reflected), computed bit-by-bit so no
+// lookup table needs to be carried around.
+fn crc32(data: &[u8]) -> u32 {
+    const POLY: u32 = 0xEDB8_8320;
+
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            let mask = 0u32.wrapping_sub(crc & 1);
+            crc = (crc >> 1) ^ (POLY & mask);
+        }
+    }
+    !crc
+}
+
+/// Frame `items`, CBOR-encoded, as a checksummed, optionally-compressed
+/// on-disk block: a u32 body length, a u8 [BlockCodec] tag, the
+/// (optionally compressed) body, and a trailing crc32 of the
+/// uncompressed CBOR bytes.
+///
+/// `codec` is only applied when the uncompressed payload is at least
+/// `min_compress_size` bytes; smaller payloads are always stored as
+/// [BlockCodec::None], since compression overhead would outweigh the
+/// savings.
+pub fn encode_block<T>(items: &[T], codec: BlockCodec, min_compress_size: usize) -> Result<Vec<u8>>
+where
+    T: Clone + IntoCbor,
+{
+    let cbor_val = items.to_vec().into_cbor()?;
+    let mut payload: Vec<u8> = vec![];
+    cbor_val.encode(&mut payload)?;
+
+    let crc = crc32(&payload);
+
+    let (codec, body) = if payload.len() < min_compress_size {
+        (BlockCodec::None, payload)
+    } else {
+        match codec {
+            BlockCodec::None => (BlockCodec::None, payload),
+            BlockCodec::Lz4 => (BlockCodec::Lz4, lz4_flex::compress_prepend_size(&payload)),
+            BlockCodec::Snappy => {
+                let body = err_at!(IOError, snap::raw::Encoder::new().compress_vec(&payload))?;
+                (BlockCodec::Snappy, body)
+            }
+        }
+    };
+
+    let mut block = Vec::with_capacity(BLOCK_HEADER_LEN + body.len() + BLOCK_TRAILER_LEN);
+    block.extend_from_slice(&err_at!(FailConvert, u32::try_from(body.len()))?.to_be_bytes());
+    block.push(codec as u8);
+    block.extend_from_slice(&body);
+    block.extend_from_slice(&crc.to_be_bytes());
+
+    Ok(block)
+}
+
+/// Inverse of [encode_block]: verify the trailing crc32 before
+/// deserializing, returning the decoded items and the number of bytes
+/// of `buf` consumed.
+pub fn decode_block<T>(buf: &[u8]) -> Result<(Vec<T>, usize)>
+where
+    T: FromCbor,
+{
+    if buf.len() < BLOCK_HEADER_LEN + BLOCK_TRAILER_LEN {
+        err_at!(FailConvert, msg: "block shorter than its framing")?;
+    }
+
+    let body_len = u32::from_be_bytes(err_at!(FailConvert, <[u8; 4]>::try_from(&buf[..4]))?) as usize;
+    let codec = BlockCodec::try_from(buf[4])?;
+
+    let total = BLOCK_HEADER_LEN + body_len + BLOCK_TRAILER_LEN;
+    if buf.len() < total {
+        err_at!(FailConvert, msg: "block truncated, need {} bytes, have {}", total, buf.len())?;
+    }
+
+    let body = &buf[BLOCK_HEADER_LEN..BLOCK_HEADER_LEN + body_len];
+    let want_crc = u32::from_be_bytes(err_at!(
+        FailConvert,
+        <[u8; 4]>::try_from(&buf[BLOCK_HEADER_LEN + body_len..total])
+    )?);
+
+    let payload = match codec {
+        BlockCodec::None => body.to_vec(),
+        BlockCodec::Lz4 => err_at!(IOError, lz4_flex::decompress_size_prepended(body))?,
+        BlockCodec::Snappy => err_at!(IOError, snap::raw::Decoder::new().decompress_vec(body))?,
+    };
+
+    let got_crc = crc32(&payload);
+    if got_crc != want_crc {
+        err_at!(
+            FailCbor,
+            msg: "block checksum mismatch, want {:x} got {:x}",
+            want_crc,
+            got_crc
+        )?;
+    }
+
+    let mut cur = &payload[..];
+    let (cbor_val, _) = Cbor::decode(&mut cur)?;
+    let items = err_at!(IOError, Vec::<T>::from_cbor(cbor_val))?;
+
+    Ok((items, total))
+}
+
 #[cfg(test)]
 #[path = "db_test.rs"]
 mod db_test;
@@ -113,3 +113,152 @@ fn test_entry_merge() {
 
     assert_eq!(one.merge(&two), entry);
 }
+
+#[test]
+fn test_entry_get_as_of() {
+    let mut entry: Entry<u8, u64, u64> = Entry::new(10, 200, 1);
+    entry.insert(300, 3);
+    entry.delete(5);
+    entry.insert(400, 7);
+
+    assert_eq!(entry.get_as_of(0), None);
+    assert_eq!(entry.get_as_of(1), Some(200));
+    assert_eq!(entry.get_as_of(2), Some(200));
+    assert_eq!(entry.get_as_of(3), Some(300));
+    assert_eq!(entry.get_as_of(4), Some(300));
+    assert_eq!(entry.get_as_of(5), None);
+    assert_eq!(entry.get_as_of(6), None);
+    assert_eq!(entry.get_as_of(7), Some(400));
+    assert_eq!(entry.get_as_of(100), Some(400));
+}
+
+#[test]
+fn test_entry_to_seqno_as_of() {
+    let mut entry: Entry<u8, u64, u64> = Entry::new(10, 200, 1);
+    entry.insert(300, 3);
+    entry.delete(5);
+
+    assert_eq!(entry.to_seqno_as_of(0), None);
+    assert_eq!(entry.to_seqno_as_of(1), Some(1));
+    assert_eq!(entry.to_seqno_as_of(4), Some(3));
+    assert_eq!(entry.to_seqno_as_of(5), Some(5));
+    assert_eq!(entry.to_seqno_as_of(100), Some(5));
+}
+
+#[test]
+fn test_merge_disjoint_keys() {
+    let one: Vec<Entry<u8, u64, u64>> = vec![Entry::new(1, 100, 1), Entry::new(3, 300, 3)];
+    let two: Vec<Entry<u8, u64, u64>> = vec![Entry::new(2, 200, 2), Entry::new(4, 400, 4)];
+
+    let sources: Vec<Box<dyn Iterator<Item = Entry<u8, u64, u64>>>> =
+        vec![Box::new(one.clone().into_iter()), Box::new(two.clone().into_iter())];
+    let merged: Vec<Entry<u8, u64, u64>> = Merge::new(sources, None).collect();
+
+    assert_eq!(merged, vec![one[0].clone(), two[0].clone(), one[1].clone(), two[1].clone()]);
+}
+
+#[test]
+fn test_merge_overlapping_keys_prefers_later_source() {
+    let mut one: Entry<u8, u64, u64> = Entry::new(10, 100, 1);
+    one.insert(101, 3);
+
+    let mut two: Entry<u8, u64, u64> = Entry::new(10, 200, 2);
+    two.insert(201, 3);
+
+    let sources: Vec<Box<dyn Iterator<Item = Entry<u8, u64, u64>>>> = vec![
+        Box::new(vec![one.clone()].into_iter()),
+        Box::new(vec![two.clone()].into_iter()),
+    ];
+    let merged: Vec<Entry<u8, u64, u64>> = Merge::new(sources, None).collect();
+
+    assert_eq!(merged, vec![one.merge(&two)]);
+    // Both sources carry seqno 3; the later source's value is applied last.
+    assert_eq!(merged[0].to_value(), Some(201));
+}
+
+#[test]
+fn test_write_batch_into_entries() {
+    let mut batch: WriteBatch<u8, u64, u64> = WriteBatch::new();
+    batch.set(1, 100);
+    batch.set(2, 200);
+    batch.set(1, 101);
+    batch.delete(2);
+
+    assert_eq!(batch.count(), 4);
+
+    let (entries, next_seqno) = batch.into_entries(10);
+    assert_eq!(next_seqno, 14);
+
+    let mut one = Entry::new(1, 100, 10);
+    one.insert(101, 12);
+    let mut two = Entry::new(2, 200, 11);
+    two.delete(13);
+
+    assert_eq!(entries, vec![one, two]);
+}
+
+#[test]
+fn test_write_batch_byte_size_grows() {
+    let mut batch: WriteBatch<u8, u64, u64> = WriteBatch::new();
+    assert_eq!(batch.byte_size().unwrap(), 0);
+
+    batch.set(1, 100);
+    let one_op = batch.byte_size().unwrap();
+    assert!(one_op > 0);
+
+    batch.set(2, 200);
+    assert!(batch.byte_size().unwrap() > one_op);
+}
+
+#[test]
+fn test_encode_decode_block_roundtrip() {
+    let entries: Vec<Entry<u8, u64, u64>> = vec![Entry::new(1, 100, 1), Entry::new(2, 200, 2)];
+
+    let block = encode_block(&entries, BlockCodec::None, 1024).unwrap();
+    let (decoded, n): (Vec<Entry<u8, u64, u64>>, usize) = decode_block(&block).unwrap();
+
+    assert_eq!(n, block.len());
+    assert_eq!(decoded, entries);
+}
+
+#[test]
+fn test_encode_decode_block_compressed() {
+    let entries: Vec<Entry<u16, u64, u64>> = (0_u16..1000)
+        .map(|i| Entry::new(i, u64::from(i), u64::from(i)))
+        .collect();
+
+    let block = encode_block(&entries, BlockCodec::Lz4, 0).unwrap();
+    let (decoded, _): (Vec<Entry<u16, u64, u64>>, usize) = decode_block(&block).unwrap();
+
+    assert_eq!(decoded, entries);
+}
+
+#[test]
+fn test_decode_block_detects_corruption() {
+    let entries: Vec<Entry<u8, u64, u64>> = vec![Entry::new(1, 100, 1)];
+    let mut block = encode_block(&entries, BlockCodec::None, 1024).unwrap();
+
+    let last = block.len() - 1;
+    block[last] ^= 0xff;
+
+    assert!(decode_block::<Entry<u8, u64, u64>>(&block).is_err());
+}
+
+#[test]
+fn test_decode_block_below_threshold_skips_compression() {
+    let entries: Vec<Entry<u8, u64, u64>> = vec![Entry::new(1, 100, 1)];
+    let block = encode_block(&entries, BlockCodec::Lz4, 1024).unwrap();
+
+    assert_eq!(block[4], BlockCodec::None as u8);
+}
+
+#[test]
+fn test_merge_with_cutoff_drops_entry() {
+    let one: Vec<Entry<u8, u64, u64>> = vec![Entry::new_deleted(1, 1)];
+    let sources: Vec<Box<dyn Iterator<Item = Entry<u8, u64, u64>>>> =
+        vec![Box::new(one.into_iter())];
+
+    let merged: Vec<Entry<u8, u64, u64>> = Merge::new(sources, Some(Cutoff::Mono)).collect();
+
+    assert!(merged.is_empty());
+}
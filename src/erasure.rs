@@ -0,0 +1,297 @@
+//! Module implement Reed-Solomon erasure coding over `GF(2^8)`.
+//!
+//! It lets a serialized blob — for example the output of
+//! [`Bloom::to_bytes`](crate::db::Bloom::to_bytes), or an encoded
+//! [`Cbor`](crate::cbor::Cbor) value — survive partial media corruption
+//! or a few missing shards. `data` is padded and split into
+//! `data_shards` equal-length pieces, and `parity_shards` extra pieces
+//! are computed so that any `data_shards` of the `data_shards +
+//! parity_shards` total shards suffice to reconstruct the original
+//! bytes.
+//!
+//! The encoding matrix is a systematic Reed-Solomon matrix: a
+//! Vandermonde matrix, one row per shard and one column per data
+//! shard, row-reduced so that its top `data_shards` rows form the
+//! identity (making the first `data_shards` output shards identical
+//! to the input, and the remaining `parity_shards` rows the parity).
+//! Reconstruction inverts the `data_shards × data_shards` submatrix
+//! picked out by whichever shards are still available, and multiplies
+//! it back against them.
+
+use std::convert::TryInto;
+
+use crate::{Error, Result};
+
+// 1-byte shard index + 8-byte original length (u64 LE).
+const HEADER_LEN: usize = 9;
+
+// Reduction polynomial x^8+x^4+x^3+x^2+1, used with generator 2.
+const GF_POLY: u16 = 0x11d;
+
+/// Split `data` into `data_shards` equal-length shards, padding `data` with
+/// zeroes to a multiple of `data_shards` first, and append `parity_shards`
+/// parity shards computed over `GF(2^8)`.
+///
+/// Each returned shard is prefixed with a small header recording its
+/// shard index and the original, un-padded length of `data`, so that
+/// [reconstruct] knows which rows of the encoding matrix to invert and
+/// how much padding to strip off the recovered bytes.
+pub fn encode_shards(data: &[u8], data_shards: usize, parity_shards: usize) -> Vec<Vec<u8>> {
+    if data_shards == 0 || parity_shards == 0 {
+        panic!("data_shards and parity_shards must be non-zero")
+    }
+    if data_shards + parity_shards > 256 {
+        panic!("data_shards + parity_shards must not exceed 256")
+    }
+
+    let shard_len = data.len().div_ceil(data_shards).max(1);
+
+    let mut shards: Vec<Vec<u8>> = (0..data_shards)
+        .map(|i| {
+            let start = (i * shard_len).min(data.len());
+            let end = (start + shard_len).min(data.len());
+            let mut shard = vec![0u8; shard_len];
+            shard[..end - start].copy_from_slice(&data[start..end]);
+            shard
+        })
+        .collect();
+
+    let gf = Gf256::new();
+    let matrix = encoding_matrix(&gf, data_shards, parity_shards);
+    for row in matrix.iter().skip(data_shards) {
+        let mut parity = vec![0u8; shard_len];
+        for (d, shard) in shards.iter().take(data_shards).enumerate() {
+            let coeff = row[d];
+            if coeff == 0 {
+                continue;
+            }
+            for (byte, &b) in parity.iter_mut().zip(shard.iter()) {
+                *byte ^= gf.mul(coeff, b);
+            }
+        }
+        shards.push(parity);
+    }
+
+    shards
+        .into_iter()
+        .enumerate()
+        .map(|(index, shard)| {
+            let mut out = Vec::with_capacity(HEADER_LEN + shard.len());
+            out.push(index as u8);
+            out.extend_from_slice(&(data.len() as u64).to_le_bytes());
+            out.extend_from_slice(&shard);
+            out
+        })
+        .collect()
+}
+
+/// Reconstruct the original bytes passed to [encode_shards] from
+/// whichever of its `data_shards + parity_shards` output shards are
+/// still available.
+///
+/// `shards` must have exactly `data_shards + parity_shards` slots, in
+/// their original shard-index order, with `None` marking a missing or
+/// corrupt shard. Fails with an error, rather than panicking, when
+/// fewer than `data_shards` shards are present.
+pub fn reconstruct(
+    shards: &mut [Option<Vec<u8>>],
+    data_shards: usize,
+    parity_shards: usize,
+) -> Result<Vec<u8>> {
+    let total_shards = data_shards + parity_shards;
+    if shards.len() != total_shards {
+        err_at!(
+            FailConvert,
+            msg: "expected {} shards, found {}",
+            total_shards,
+            shards.len()
+        )?;
+    }
+
+    let available: Vec<usize> = shards
+        .iter()
+        .enumerate()
+        .filter_map(|(i, shard)| shard.as_ref().map(|_| i))
+        .collect();
+    // `available.len() < data_shards` alone misses the degenerate
+    // `data_shards == 0` case with no shards present (`0 < 0` is
+    // false), which would otherwise panic on `available[0]` below.
+    if available.is_empty() || available.len() < data_shards {
+        err_at!(
+            FailConvert,
+            msg: "need at least {} shards to reconstruct, found {}",
+            data_shards,
+            available.len()
+        )?;
+    }
+
+    let (orig_len, shard_len) = {
+        let shard = shards[available[0]].as_ref().unwrap();
+        if shard.len() < HEADER_LEN {
+            err_at!(FailConvert, msg: "shard {} is shorter than its header", available[0])?;
+        }
+        let orig_len = u64::from_le_bytes(shard[1..HEADER_LEN].try_into().unwrap()) as usize;
+        (orig_len, shard.len() - HEADER_LEN)
+    };
+
+    let gf = Gf256::new();
+    let full_matrix = encoding_matrix(&gf, data_shards, parity_shards);
+
+    let chosen: Vec<usize> = available.into_iter().take(data_shards).collect();
+    for &i in chosen.iter() {
+        let len = shards[i].as_ref().unwrap().len();
+        if len != shard_len + HEADER_LEN {
+            err_at!(
+                FailConvert,
+                msg: "shard {} has length {}, expected {}",
+                i,
+                len,
+                shard_len + HEADER_LEN
+            )?;
+        }
+    }
+    let sub_matrix: Vec<Vec<u8>> = chosen.iter().map(|&i| full_matrix[i].clone()).collect();
+    let inverse = invert(&gf, &sub_matrix)?;
+
+    let mut data_rows = vec![vec![0u8; shard_len]; data_shards];
+    for (out_row, row) in data_rows.iter_mut().enumerate() {
+        for (col, &src_index) in chosen.iter().enumerate() {
+            let coeff = inverse[out_row][col];
+            if coeff == 0 {
+                continue;
+            }
+            let shard = shards[src_index].as_ref().unwrap();
+            for (byte, &b) in row.iter_mut().zip(shard[HEADER_LEN..].iter()) {
+                *byte ^= gf.mul(coeff, b);
+            }
+        }
+    }
+
+    let mut out: Vec<u8> = data_rows.into_iter().flatten().collect();
+    out.truncate(orig_len);
+    Ok(out)
+}
+
+// Arithmetic over `GF(2^8)`, backed by precomputed log/antilog tables for
+// the primitive polynomial `GF_POLY` and generator 2.
+struct Gf256 {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+impl Gf256 {
+    fn new() -> Self {
+        let mut exp = [0u8; 512];
+        let mut log = [0u8; 256];
+
+        let mut x: u16 = 1;
+        for (i, exp) in exp.iter_mut().enumerate().take(255) {
+            *exp = x as u8;
+            log[x as usize] = i as u8;
+            x <<= 1;
+            if x & 0x100 != 0 {
+                x ^= GF_POLY;
+            }
+        }
+        for i in 255..512 {
+            exp[i] = exp[i - 255];
+        }
+
+        Gf256 { exp, log }
+    }
+
+    fn mul(&self, a: u8, b: u8) -> u8 {
+        if a == 0 || b == 0 {
+            0
+        } else {
+            let i = self.log[a as usize] as usize + self.log[b as usize] as usize;
+            self.exp[i]
+        }
+    }
+
+    fn inv(&self, a: u8) -> u8 {
+        // Caller guarantees `a != 0`.
+        self.exp[255 - self.log[a as usize] as usize]
+    }
+}
+
+// Systematic Reed-Solomon encoding matrix: `(data_shards + parity_shards)`
+// rows by `data_shards` columns, with the top `data_shards` rows forming
+// the identity matrix.
+fn encoding_matrix(gf: &Gf256, data_shards: usize, parity_shards: usize) -> Vec<Vec<u8>> {
+    let n = data_shards + parity_shards;
+    let k = data_shards;
+
+    // Vandermonde matrix: row i uses field element `x = (i + 1) as u8`,
+    // distinct for every row since `n <= 256` (only the 256th row of a
+    // 256-shard matrix wraps to 0), column j holds `x^j`.
+    let vandermonde: Vec<Vec<u8>> = (0..n)
+        .map(|i| {
+            let x = (i + 1) as u8;
+            let mut row = vec![1u8; k];
+            for j in 1..k {
+                row[j] = gf.mul(row[j - 1], x);
+            }
+            row
+        })
+        .collect();
+
+    // Any k x k submatrix of a Vandermonde matrix built from distinct,
+    // non-zero field elements is invertible, so this never fails.
+    let top_inverse = invert(gf, &vandermonde[..k])
+        .expect("top k rows of a Vandermonde matrix are always invertible");
+
+    (0..n)
+        .map(|i| {
+            (0..k)
+                .map(|col| {
+                    (0..k).fold(0u8, |acc, t| acc ^ gf.mul(vandermonde[i][t], top_inverse[t][col]))
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Gauss-Jordan elimination over `GF(2^8)`.
+fn invert(gf: &Gf256, m: &[Vec<u8>]) -> Result<Vec<Vec<u8>>> {
+    let n = m.len();
+    let mut a: Vec<Vec<u8>> = m.to_vec();
+    let mut inverse: Vec<Vec<u8>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1 } else { 0 }).collect())
+        .collect();
+
+    for col in 0..n {
+        let pivot = match (col..n).find(|&r| a[r][col] != 0) {
+            Some(pivot) => pivot,
+            None => err_at!(Fatal, msg: "matrix is singular, cannot invert")?,
+        };
+        a.swap(col, pivot);
+        inverse.swap(col, pivot);
+
+        let pivot_inv = gf.inv(a[col][col]);
+        for j in 0..n {
+            a[col][j] = gf.mul(a[col][j], pivot_inv);
+            inverse[col][j] = gf.mul(inverse[col][j], pivot_inv);
+        }
+
+        for r in 0..n {
+            if r == col {
+                continue;
+            }
+            let factor = a[r][col];
+            if factor == 0 {
+                continue;
+            }
+            for j in 0..n {
+                a[r][j] ^= gf.mul(factor, a[col][j]);
+                inverse[r][j] ^= gf.mul(factor, inverse[col][j]);
+            }
+        }
+    }
+
+    Ok(inverse)
+}
+
+#[cfg(test)]
+#[path = "erasure_test.rs"]
+mod erasure_test;
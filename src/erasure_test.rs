@@ -0,0 +1,140 @@
+use super::*;
+
+#[test]
+fn test_gf256_mul_inv_roundtrip() {
+    let gf = Gf256::new();
+    for a in 1..=255u8 {
+        assert_eq!(gf.mul(a, gf.inv(a)), 1, "a={}", a);
+    }
+    assert_eq!(gf.mul(0, 42), 0);
+    assert_eq!(gf.mul(42, 0), 0);
+}
+
+#[test]
+fn test_encode_reconstruct_exact_shards() {
+    let data = b"the quick brown fox jumps over the lazy dog".to_vec();
+    let shards = encode_shards(&data, 4, 2);
+    assert_eq!(shards.len(), 6);
+
+    let mut inputs: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    let got = reconstruct(&mut inputs, 4, 2).unwrap();
+    assert_eq!(got, data);
+}
+
+#[test]
+fn test_reconstruct_survives_missing_data_shards() {
+    let data = (0..257u32).map(|i| i as u8).collect::<Vec<_>>();
+    let shards = encode_shards(&data, 5, 3);
+
+    let mut inputs: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    // Drop two data shards and one parity shard; 5 of 8 remain.
+    inputs[0] = None;
+    inputs[2] = None;
+    inputs[6] = None;
+
+    let got = reconstruct(&mut inputs, 5, 3).unwrap();
+    assert_eq!(got, data);
+}
+
+#[test]
+fn test_reconstruct_survives_all_parity_missing() {
+    let data = b"0123456789abcdef".to_vec();
+    let shards = encode_shards(&data, 4, 4);
+
+    let mut inputs: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    inputs[4] = None;
+    inputs[5] = None;
+    inputs[6] = None;
+    inputs[7] = None;
+
+    let got = reconstruct(&mut inputs, 4, 4).unwrap();
+    assert_eq!(got, data);
+}
+
+#[test]
+fn test_reconstruct_survives_all_data_missing() {
+    let data = b"0123456789abcdef".to_vec();
+    let shards = encode_shards(&data, 4, 4);
+
+    let mut inputs: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    inputs[0] = None;
+    inputs[1] = None;
+    inputs[2] = None;
+    inputs[3] = None;
+
+    let got = reconstruct(&mut inputs, 4, 4).unwrap();
+    assert_eq!(got, data);
+}
+
+#[test]
+fn test_reconstruct_fails_cleanly_with_too_few_shards() {
+    let data = b"not enough shards".to_vec();
+    let shards = encode_shards(&data, 4, 2);
+
+    let mut inputs: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    inputs[0] = None;
+    inputs[1] = None;
+    inputs[2] = None;
+
+    assert!(reconstruct(&mut inputs, 4, 2).is_err());
+}
+
+#[test]
+fn test_reconstruct_rejects_wrong_shard_count() {
+    let data = b"fixed shard count".to_vec();
+    let shards = encode_shards(&data, 4, 2);
+
+    let mut inputs: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    inputs.pop();
+
+    assert!(reconstruct(&mut inputs, 4, 2).is_err());
+}
+
+#[test]
+fn test_encode_pads_data_not_a_multiple_of_shard_count() {
+    let data = b"12345".to_vec(); // not a multiple of 4
+    let shards = encode_shards(&data, 4, 2);
+
+    let shard_len = shards[0].len() - 9;
+    for shard in &shards {
+        assert_eq!(shard.len() - 9, shard_len);
+    }
+
+    let mut inputs: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    let got = reconstruct(&mut inputs, 4, 2).unwrap();
+    assert_eq!(got, data);
+}
+
+#[test]
+fn test_reconstruct_rejects_truncated_shard() {
+    let data = b"the quick brown fox jumps over the lazy dog".to_vec();
+    let shards = encode_shards(&data, 4, 2);
+
+    let mut inputs: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    // Truncate one of the chosen shards so its length no longer matches
+    // its peers; this must be caught as an error, not silently XORed
+    // in as a short, wrong-length accumulation.
+    if let Some(shard) = inputs[0].as_mut() {
+        shard.pop();
+    }
+
+    assert!(reconstruct(&mut inputs, 4, 2).is_err());
+}
+
+#[test]
+fn test_reconstruct_rejects_empty_shards_with_zero_data_shards() {
+    let mut inputs: Vec<Option<Vec<u8>>> = vec![];
+    assert!(reconstruct(&mut inputs, 0, 0).is_err());
+}
+
+#[test]
+fn test_encode_reconstruct_empty_data() {
+    let data: Vec<u8> = vec![];
+    let shards = encode_shards(&data, 3, 2);
+
+    let mut inputs: Vec<Option<Vec<u8>>> = shards.into_iter().map(Some).collect();
+    inputs[1] = None;
+
+    let got = reconstruct(&mut inputs, 3, 2).unwrap();
+    assert_eq!(got, data);
+}
@@ -0,0 +1,153 @@
+//! Module implement a Merkle hash-tree over chunked byte payloads.
+//!
+//! To support the non-destructive, versioned writes that [Diff][crate::data::Diff]
+//! is designed for, a large encoded [Cbor][crate::cbor::Cbor] value, or a
+//! [Bloom][crate::db::Bloom] bitmap, can be chunked into leaves and hashed
+//! into a [MerkleTree]. Two versions of the same value that share a
+//! prefix produce overlapping subtrees, so comparing two trees' leaf
+//! hashes is a cheap way to find exactly which chunks a [Diff::diff]
+//! changed, without re-hashing, or even holding in memory, the parts
+//! that didn't.
+//!
+//! A domain-separation byte distinguishes a leaf hash from an internal
+//! node hash, so that a leaf can never be mistaken for, and substituted
+//! by, an internal node with the same hash (a second-preimage attack).
+//! An odd-length level duplicates its last node rather than leaving it
+//! unpaired.
+
+use sha2::{Digest, Sha256};
+
+const LEAF_DOMAIN: u8 = 0x00;
+const NODE_DOMAIN: u8 = 0x01;
+
+/// Which side of the current hash a proof step's sibling sits on.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// Pluggable digest used to hash [MerkleTree] leaves and internal nodes.
+///
+/// `domain` is mixed in ahead of `parts` so that leaf and internal-node
+/// hashes never collide.
+pub trait MerkleHasher {
+    fn hash(&self, domain: u8, parts: &[&[u8]]) -> [u8; 32];
+}
+
+/// Default [MerkleHasher], backed by SHA-256.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sha256Hasher;
+
+impl MerkleHasher for Sha256Hasher {
+    fn hash(&self, domain: u8, parts: &[&[u8]]) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update([domain]);
+        for part in parts {
+            hasher.update(part);
+        }
+        hasher.finalize().into()
+    }
+}
+
+/// A balanced binary hash-tree over a slice of leaves.
+pub struct MerkleTree {
+    // `levels[0]` holds the hashed leaves, `levels.last()` the
+    // single-element root level.
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    /// Build a tree over `leaves`, hashing each with [Sha256Hasher].
+    pub fn build(leaves: &[Vec<u8>]) -> Self {
+        MerkleTree::build_with(leaves, &Sha256Hasher)
+    }
+
+    /// Build a tree over `leaves`, hashing each with `hasher`.
+    pub fn build_with<H>(leaves: &[Vec<u8>], hasher: &H) -> Self
+    where
+        H: MerkleHasher,
+    {
+        let mut level: Vec<[u8; 32]> = if leaves.is_empty() {
+            vec![hasher.hash(LEAF_DOMAIN, &[&[]])]
+        } else {
+            leaves
+                .iter()
+                .map(|leaf| hasher.hash(LEAF_DOMAIN, &[leaf.as_slice()]))
+                .collect()
+        };
+
+        let mut levels = vec![level.clone()];
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hasher.hash(NODE_DOMAIN, &[&a[..], &b[..]]),
+                    [a] => hasher.hash(NODE_DOMAIN, &[&a[..], &a[..]]),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level.clone());
+        }
+
+        MerkleTree { levels }
+    }
+
+    /// Return the root hash of the tree.
+    pub fn root(&self) -> [u8; 32] {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    /// Return the sibling hashes, from `index`'s leaf up to the root,
+    /// needed to re-derive [MerkleTree::root] from that leaf alone.
+    pub fn proof(&self, mut index: usize) -> Vec<([u8; 32], Side)> {
+        let mut path = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let (sibling_index, side) = if index.is_multiple_of(2) {
+                (index + 1, Side::Right)
+            } else {
+                (index - 1, Side::Left)
+            };
+            let sibling = *level.get(sibling_index).unwrap_or(&level[index]);
+            path.push((sibling, side));
+            index /= 2;
+        }
+        path
+    }
+
+    /// Recompute the path from `leaf` using `proof`, hashed with
+    /// [Sha256Hasher], and check that it lands on `root`.
+    pub fn verify(leaf: &[u8], index: usize, proof: &[([u8; 32], Side)], root: [u8; 32]) -> bool {
+        MerkleTree::verify_with(&Sha256Hasher, leaf, index, proof, root)
+    }
+
+    /// [MerkleTree::verify], hashed with `hasher` instead of
+    /// [Sha256Hasher].
+    ///
+    /// `index` is the leaf's position; it is not needed to recompute
+    /// the path, since each proof step already carries its [Side], but
+    /// is taken for symmetry with [MerkleTree::proof].
+    pub fn verify_with<H>(
+        hasher: &H,
+        leaf: &[u8],
+        _index: usize,
+        proof: &[([u8; 32], Side)],
+        root: [u8; 32],
+    ) -> bool
+    where
+        H: MerkleHasher,
+    {
+        let mut hash = hasher.hash(LEAF_DOMAIN, &[leaf]);
+        for (sibling, side) in proof {
+            hash = match side {
+                Side::Left => hasher.hash(NODE_DOMAIN, &[&sibling[..], &hash[..]]),
+                Side::Right => hasher.hash(NODE_DOMAIN, &[&hash[..], &sibling[..]]),
+            };
+        }
+        hash == root
+    }
+}
+
+#[cfg(test)]
+#[path = "merkle_test.rs"]
+mod merkle_test;
@@ -0,0 +1,94 @@
+use super::*;
+
+fn leaves(n: usize) -> Vec<Vec<u8>> {
+    (0..n).map(|i| vec![i as u8; 4]).collect()
+}
+
+#[test]
+fn test_single_leaf_root_is_its_own_hash() {
+    let tree = MerkleTree::build(&leaves(1));
+    let expected = Sha256Hasher.hash(LEAF_DOMAIN, &[&[0u8; 4][..]]);
+    assert_eq!(tree.root(), expected);
+}
+
+#[test]
+fn test_proof_verify_roundtrip_even_leaf_count() {
+    let data = leaves(8);
+    let tree = MerkleTree::build(&data);
+    let root = tree.root();
+    for (i, leaf) in data.iter().enumerate() {
+        let proof = tree.proof(i);
+        assert!(MerkleTree::verify(leaf, i, &proof, root));
+    }
+}
+
+#[test]
+fn test_proof_verify_roundtrip_odd_leaf_count() {
+    let data = leaves(5);
+    let tree = MerkleTree::build(&data);
+    let root = tree.root();
+    for (i, leaf) in data.iter().enumerate() {
+        let proof = tree.proof(i);
+        assert!(MerkleTree::verify(leaf, i, &proof, root));
+    }
+}
+
+#[test]
+fn test_verify_fails_on_tampered_leaf() {
+    let data = leaves(6);
+    let tree = MerkleTree::build(&data);
+    let root = tree.root();
+    let proof = tree.proof(2);
+    let tampered = vec![99u8; 4];
+    assert!(!MerkleTree::verify(&tampered, 2, &proof, root));
+}
+
+#[test]
+fn test_verify_fails_on_tampered_proof() {
+    let data = leaves(6);
+    let tree = MerkleTree::build(&data);
+    let root = tree.root();
+    let mut proof = tree.proof(2);
+    proof[0].0[0] ^= 0xff;
+    assert!(!MerkleTree::verify(&data[2], 2, &proof, root));
+}
+
+#[test]
+fn test_leaf_hash_never_equals_node_hash_of_same_bytes() {
+    let a = [1u8; 32];
+    let b = [2u8; 32];
+    let leaf_hash = Sha256Hasher.hash(LEAF_DOMAIN, &[&a[..], &b[..]]);
+    let node_hash = Sha256Hasher.hash(NODE_DOMAIN, &[&a[..], &b[..]]);
+    assert_ne!(leaf_hash, node_hash);
+}
+
+#[test]
+fn test_build_with_custom_hasher() {
+    #[derive(Clone, Copy, Default)]
+    struct XorHasher;
+
+    impl MerkleHasher for XorHasher {
+        fn hash(&self, domain: u8, parts: &[&[u8]]) -> [u8; 32] {
+            let mut out = [domain; 32];
+            for part in parts {
+                for (i, &b) in part.iter().enumerate() {
+                    out[i % 32] ^= b;
+                }
+            }
+            out
+        }
+    }
+
+    let data = leaves(4);
+    let tree = MerkleTree::build_with(&data, &XorHasher);
+    let root = tree.root();
+    let proof = tree.proof(3);
+    assert!(MerkleTree::verify_with(&XorHasher, &data[3], 3, &proof, root));
+}
+
+#[test]
+fn test_different_trees_produce_different_roots() {
+    let a = MerkleTree::build(&leaves(4));
+    let b = MerkleTree::build(&leaves(5));
+    assert_ne!(a.root(), b.root());
+}
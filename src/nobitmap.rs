@@ -31,6 +31,13 @@ impl Bloom for NoBitmap {
         Ok((NoBitmap, 0))
     }
 
+    fn from_buf<B>(_buf: &mut B) -> Result<Option<(Self, usize)>, Self::Err>
+    where
+        B: crate::cbor::Buf,
+    {
+        Ok(Some((NoBitmap, 0)))
+    }
+
     /// Merge two bitmaps.
     fn or(&self, _other: &Self) -> Result<Self, Self::Err> {
         Ok(NoBitmap)
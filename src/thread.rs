@@ -9,7 +9,7 @@ use log::debug;
 #[allow(unused_imports)]
 use std::{
     mem,
-    sync::{mpsc, Arc},
+    sync::{atomic, atomic::AtomicUsize, mpsc, Arc, Condvar, Mutex, RwLock},
     thread,
 };
 
@@ -21,8 +21,8 @@ use crate::{Error, Result};
 /// The clone behavior is similar to [std::sync::mpsc::Sender] or,
 /// [std::sync::mpsc::Sender].
 pub enum Tx<Q, R> {
-    N(mpsc::Sender<(Q, Option<mpsc::Sender<R>>)>),
-    S(mpsc::SyncSender<(Q, Option<mpsc::Sender<R>>)>),
+    N(mpsc::Sender<(Q, Option<OneSender<R>>)>),
+    S(mpsc::SyncSender<(Q, Option<OneSender<R>>)>),
 }
 
 impl<Q, R> Clone for Tx<Q, R> {
@@ -46,19 +46,103 @@ impl<Q, R> Tx<Q, R> {
 
     /// Send a request message to thread and wait for a response.
     pub fn request(&self, request: Q) -> Result<R> {
-        let (stx, srx) = mpsc::channel();
+        let (stx, srx) = oneshot();
         match self {
             Tx::N(tx) => err_at!(IPCFail, tx.send((request, Some(stx))))?,
             Tx::S(tx) => err_at!(IPCFail, tx.send((request, Some(stx))))?,
         }
-        Ok(err_at!(IPCFail, srx.recv())?)
+        srx.recv()
     }
 }
 
 /// IPC type, that shall be passed to the thread's main loop.
 ///
 /// Refer to [Thread::new] for details.
-pub type Rx<Q, R> = mpsc::Receiver<(Q, Option<mpsc::Sender<R>>)>;
+pub type Rx<Q, R> = mpsc::Receiver<(Q, Option<OneSender<R>>)>;
+
+/// Create a one-shot reply channel: at most one value is ever sent, and
+/// it is delivered to at most one receiver.
+///
+/// This is used as the reply side of [Tx::request], replacing a
+/// full [mpsc::channel] so that the common request/response path does
+/// not pay for an unbounded queue, and so that a dropped [OneSender]
+/// is observable by [OneReceiver::recv] as an explicit "request
+/// cancelled" error instead of an opaque [std::sync::mpsc::RecvError].
+pub fn oneshot<R>() -> (OneSender<R>, OneReceiver<R>) {
+    let inner = Arc::new(OneShotInner {
+        slot: Mutex::new(OneShotSlot::Empty),
+        cond: Condvar::new(),
+    });
+    let tx = OneSender {
+        inner: Arc::clone(&inner),
+    };
+    let rx = OneReceiver { inner };
+    (tx, rx)
+}
+
+enum OneShotSlot<R> {
+    Empty,
+    Sent(R),
+    Closed,
+}
+
+struct OneShotInner<R> {
+    slot: Mutex<OneShotSlot<R>>,
+    cond: Condvar,
+}
+
+/// Sending half of a [oneshot] channel.
+pub struct OneSender<R> {
+    inner: Arc<OneShotInner<R>>,
+}
+
+/// Receiving half of a [oneshot] channel.
+pub struct OneReceiver<R> {
+    inner: Arc<OneShotInner<R>>,
+}
+
+impl<R> OneSender<R> {
+    /// Send the reply value, waking up the blocked [OneReceiver::recv], if
+    /// any.
+    pub fn send(self, value: R) {
+        let mut slot = self.inner.slot.lock().expect("lock poisoned");
+        *slot = OneShotSlot::Sent(value);
+        self.inner.cond.notify_one();
+    }
+}
+
+impl<R> Drop for OneSender<R> {
+    fn drop(&mut self) {
+        // If a value was already sent, this is a no-op: `send()` takes
+        // `self` by value, so `Drop` still runs right after, but finds
+        // the slot already filled. Otherwise, mark the slot closed and
+        // wake the receiver so it observes the cancellation instead of
+        // blocking forever.
+        let mut slot = self.inner.slot.lock().expect("lock poisoned");
+        if let OneShotSlot::Empty = *slot {
+            *slot = OneShotSlot::Closed;
+            self.inner.cond.notify_one();
+        }
+    }
+}
+
+impl<R> OneReceiver<R> {
+    /// Block until the reply is available, or until the [OneSender] is
+    /// dropped without sending one, in which case an error is returned.
+    pub fn recv(self) -> Result<R> {
+        let mut slot = self.inner.slot.lock().expect("lock poisoned");
+        loop {
+            match mem::replace(&mut *slot, OneShotSlot::Empty) {
+                OneShotSlot::Sent(value) => return Ok(value),
+                OneShotSlot::Closed => {
+                    err_at!(IPCFail, msg: "request cancelled, reply sender dropped")?
+                }
+                OneShotSlot::Empty => (),
+            }
+            slot = err_at!(IPCFail, self.inner.cond.wait(slot))?;
+        }
+    }
+}
 
 /// Thread type, providing gen-server pattern to do multi-threading.
 ///
@@ -163,3 +247,127 @@ impl<Q, R, T> Thread<Q, R, T> {
         self.inner.take().unwrap().close_wait()
     }
 }
+
+/// Create a conflating state-broadcast channel, seeded with `init`.
+///
+/// Unlike [Tx]/[Rx], which hand off one message to one receiver, `watch`
+/// lets a server cheaply publish its latest state to any number of
+/// observers: [WatchTx::publish] replaces the current value and any
+/// [WatchRx] clone can [WatchRx::borrow] it or [WatchRx::changed] to
+/// wait for a newer one. Publishes are conflated, a receiver that calls
+/// `changed()` after several publishes only observes the latest value.
+pub fn watch<S>(init: S) -> (WatchTx<S>, WatchRx<S>) {
+    let inner = Arc::new(WatchInner {
+        state: RwLock::new((init, 0)),
+        guard: Mutex::new(()),
+        cond: Condvar::new(),
+        n_tx: AtomicUsize::new(1),
+    });
+    let tx = WatchTx {
+        inner: Arc::clone(&inner),
+    };
+    let rx = WatchRx { inner, seen: 0 };
+    (tx, rx)
+}
+
+struct WatchInner<S> {
+    // (value, version), version increments on every publish.
+    state: RwLock<(S, u64)>,
+    // paired with `cond`, purely to serialise changed()/publish() so that
+    // no notification is lost; `state` itself is read directly by
+    // `borrow()` without going through this lock.
+    guard: Mutex<()>,
+    cond: Condvar,
+    n_tx: AtomicUsize,
+}
+
+/// Publishing half of a [watch] channel.
+pub struct WatchTx<S> {
+    inner: Arc<WatchInner<S>>,
+}
+
+impl<S> Clone for WatchTx<S> {
+    fn clone(&self) -> Self {
+        self.inner.n_tx.fetch_add(1, atomic::Ordering::SeqCst);
+        WatchTx {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<S> Drop for WatchTx<S> {
+    fn drop(&mut self) {
+        if self.inner.n_tx.fetch_sub(1, atomic::Ordering::SeqCst) == 1 {
+            // last WatchTx gone, wake every waiter so it can observe
+            // the channel is closed.
+            let _guard = self.inner.guard.lock().expect("lock poisoned");
+            self.inner.cond.notify_all();
+        }
+    }
+}
+
+impl<S> WatchTx<S> {
+    /// Publish a new value, conflating with any value published since
+    /// the last time a [WatchRx] observed one.
+    pub fn publish(&self, value: S) {
+        let _guard = self.inner.guard.lock().expect("lock poisoned");
+        {
+            let mut state = self.inner.state.write().expect("lock poisoned");
+            state.0 = value;
+            state.1 += 1;
+        }
+        self.inner.cond.notify_all();
+    }
+}
+
+/// Observing half of a [watch] channel. Each clone tracks its own
+/// last-seen version, independent of other clones.
+pub struct WatchRx<S> {
+    inner: Arc<WatchInner<S>>,
+    seen: u64,
+}
+
+impl<S> Clone for WatchRx<S> {
+    fn clone(&self) -> Self {
+        WatchRx {
+            inner: Arc::clone(&self.inner),
+            seen: self.seen,
+        }
+    }
+}
+
+impl<S> WatchRx<S> {
+    /// Block until a value newer than the last one this [WatchRx]
+    /// observed is published, or until every [WatchTx] has been
+    /// dropped, in which case an error is returned.
+    pub fn changed(&mut self) -> Result<()> {
+        let mut guard = self.inner.guard.lock().expect("lock poisoned");
+        loop {
+            let version = self.inner.state.read().expect("lock poisoned").1;
+            if version > self.seen {
+                self.seen = version;
+                return Ok(());
+            }
+            if self.inner.n_tx.load(atomic::Ordering::SeqCst) == 0 {
+                err_at!(IPCFail, msg: "watch channel closed, no publisher remains")?
+            }
+            guard = err_at!(IPCFail, self.inner.cond.wait(guard))?;
+        }
+    }
+}
+
+impl<S> WatchRx<S>
+where
+    S: Clone,
+{
+    /// Return the most recently published value, without waiting.
+    pub fn borrow(&mut self) -> S {
+        let state = self.inner.state.read().expect("lock poisoned");
+        self.seen = state.1;
+        state.0.clone()
+    }
+}
+
+#[cfg(test)]
+#[path = "thread_test.rs"]
+mod thread_test;
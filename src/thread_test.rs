@@ -0,0 +1,102 @@
+use std::{thread, time::Duration};
+
+use super::*;
+
+#[test]
+fn test_oneshot_send_recv() {
+    let (tx, rx) = oneshot::<u64>();
+    tx.send(42);
+    assert_eq!(rx.recv().unwrap(), 42);
+}
+
+#[test]
+fn test_oneshot_cancelled_on_drop() {
+    let (tx, rx) = oneshot::<u64>();
+    mem::drop(tx);
+    assert!(rx.recv().is_err());
+}
+
+#[test]
+fn test_oneshot_blocks_until_sent() {
+    let (tx, rx) = oneshot::<u64>();
+    let handle = thread::spawn(move || rx.recv());
+    thread::sleep(Duration::from_millis(20));
+    tx.send(7);
+    assert_eq!(handle.join().unwrap().unwrap(), 7);
+}
+
+#[test]
+fn test_thread_post_and_request() {
+    let thrd = Thread::new("test_thread", |rx: Rx<u64, u64>| {
+        move || {
+            for (msg, reply) in rx.iter() {
+                if let Some(reply) = reply {
+                    reply.send(msg * 2);
+                }
+            }
+        }
+    });
+
+    let tx = thrd.clone_tx();
+    tx.post(1).unwrap();
+    assert_eq!(tx.request(10).unwrap(), 20);
+    assert_eq!(tx.request(21).unwrap(), 42);
+
+    mem::drop(tx);
+    thrd.close_wait().unwrap();
+}
+
+#[test]
+fn test_watch_publish_and_borrow() {
+    let (tx, mut rx) = watch(0_u64);
+    assert_eq!(rx.borrow(), 0);
+
+    tx.publish(1);
+    tx.publish(2);
+    assert_eq!(rx.borrow(), 2);
+}
+
+#[test]
+fn test_watch_changed_conflates_publishes() {
+    let (tx, mut rx) = watch(0_u64);
+    rx.borrow();
+
+    tx.publish(1);
+    tx.publish(2);
+    tx.publish(3);
+
+    rx.changed().unwrap();
+    assert_eq!(rx.borrow(), 3);
+}
+
+#[test]
+fn test_watch_multiple_receivers_independent() {
+    let (tx, mut rx1) = watch(0_u64);
+    let mut rx2 = rx1.clone();
+
+    tx.publish(1);
+    assert_eq!(rx1.borrow(), 1);
+
+    tx.publish(2);
+    assert_eq!(rx2.borrow(), 2);
+}
+
+#[test]
+fn test_watch_changed_blocks_until_publish() {
+    let (tx, mut rx) = watch(0_u64);
+    let handle = thread::spawn(move || {
+        rx.changed().unwrap();
+        rx.borrow()
+    });
+
+    thread::sleep(Duration::from_millis(20));
+    tx.publish(9);
+    assert_eq!(handle.join().unwrap(), 9);
+}
+
+#[test]
+fn test_watch_closed_when_all_tx_dropped() {
+    let (tx, mut rx) = watch(0_u64);
+    mem::drop(tx);
+    assert!(rx.changed().is_err());
+}
@@ -1,4 +1,4 @@
-use xorfilter::Xor8;
+use xorfilter::{Xor16, Xor8};
 
 use std::{
     hash::{BuildHasher, Hash},
@@ -19,10 +19,16 @@ struct CborXor8 {
     seed: u64,
     block_length: u32,
     finger_prints: Vec<u8>,
+    // Retained 64-bit digests fed into this filter via `add_key`/
+    // `add_digest32`, carried over the wire so that `Bloom::or` can
+    // rebuild a union filter from the combined key-set. `None` when
+    // the filter was built without retaining its keys, e.g. decoded
+    // from an older serialization.
+    keys: Option<Vec<u64>>,
 }
 
 impl CborXor8 {
-    const ID: &'static str = "xor8/0.0.1";
+    const ID: &'static str = "xor8/0.0.2";
 }
 
 impl<H> IntoCbor for Xor8<H>
@@ -35,6 +41,7 @@ where
             seed: self.seed,
             block_length: self.block_length,
             finger_prints: self.finger_prints,
+            keys: self.keys,
         };
         val.into_cbor()
     }
@@ -54,6 +61,7 @@ where
             filter.seed = val.seed;
             filter.block_length = val.block_length;
             filter.finger_prints = val.finger_prints;
+            filter.keys = val.keys;
         }
         Ok(filter)
     }
@@ -87,6 +95,142 @@ where
             seed: self.seed,
             block_length: self.block_length,
             finger_prints: self.finger_prints.clone(),
+            keys: self.keys.clone(),
+        };
+        let cbor_val = err_at!(IOError, val.into_cbor())?;
+
+        let mut buf: Vec<u8> = vec![];
+        err_at!(IOError, cbor_val.encode_self_described(&mut buf))?;
+        Ok(buf)
+    }
+
+    fn from_bytes(mut buf: &[u8]) -> result::Result<(Self, usize), Self::Err> {
+        let (val, n) = err_at!(IOError, Cbor::decode_self_described(&mut buf))?;
+        Ok((err_at!(IOError, Xor8::<H>::from_cbor(val))?, n))
+    }
+
+    fn from_buf<B>(buf: &mut B) -> result::Result<Option<(Self, usize)>, Self::Err>
+    where
+        B: crate::cbor::Buf,
+    {
+        match err_at!(IOError, Cbor::decode_self_described_buf(buf))? {
+            None => Ok(None),
+            Some((val, n)) => {
+                let filter = err_at!(IOError, Xor8::<H>::from_cbor(val))?;
+                Ok(Some((filter, n)))
+            }
+        }
+    }
+
+    // Xor filters are static once `build()` runs: the fingerprint array
+    // cannot be merged bit-wise like a classic bloom filter. The only
+    // sound union is to rebuild from the combined key-set retained by
+    // each operand.
+    fn or(&self, other: &Self) -> result::Result<Self, Self::Err> {
+        let (one, two) = match (self.keys.as_ref(), other.keys.as_ref()) {
+            (Some(one), Some(two)) => (one, two),
+            (_, _) => err_at!(
+                Fatal,
+                msg: "cannot merge xor8 filter without retained keys"
+            )?,
+        };
+
+        let mut keys: Vec<u64> = Vec::with_capacity(one.len() + two.len());
+        keys.extend(one.iter().copied());
+        keys.extend(two.iter().copied());
+        keys.sort_unstable();
+        keys.dedup();
+
+        let mut filter = Xor8::<H>::default();
+        filter.populate_keys(&keys);
+        err_at!(Fatal, filter.build())?;
+        Ok(filter)
+    }
+}
+
+// Intermediate type to serialize and de-serialized Xor16 into bytes using
+// `mkit` macros. Carrying 16-bit fingerprints, as opposed to `CborXor8`'s
+// 8-bit ones, pushes the false-positive rate down from ~1/256 to ~1/65536.
+#[derive(LocalCborize)]
+struct CborXor16 {
+    hash_builder: Vec<u8>,
+    seed: u64,
+    block_length: u32,
+    finger_prints: Vec<u16>,
+    keys: Option<Vec<u64>>,
+}
+
+impl CborXor16 {
+    // Distinct from `CborXor8::ID` so that a 16-bit filter can never be
+    // mistaken for, or decoded as, an 8-bit one.
+    const ID: &'static str = "xor16/0.0.1";
+}
+
+impl<H> IntoCbor for Xor16<H>
+where
+    H: BuildHasher + Into<Vec<u8>>,
+{
+    fn into_cbor(self) -> Result<Cbor> {
+        let val = CborXor16 {
+            hash_builder: self.hash_builder.into(),
+            seed: self.seed,
+            block_length: self.block_length,
+            finger_prints: self.finger_prints,
+            keys: self.keys,
+        };
+        val.into_cbor()
+    }
+}
+
+impl<H> FromCbor for Xor16<H>
+where
+    H: Default + BuildHasher + From<Vec<u8>>,
+{
+    fn from_cbor(val: Cbor) -> Result<Self> {
+        let val = CborXor16::from_cbor(val)?;
+
+        let mut filter = Xor16::<H>::default();
+        #[allow(clippy::field_reassign_with_default)]
+        {
+            filter.hash_builder = val.hash_builder.into();
+            filter.seed = val.seed;
+            filter.block_length = val.block_length;
+            filter.finger_prints = val.finger_prints;
+            filter.keys = val.keys;
+        }
+        Ok(filter)
+    }
+}
+
+impl<H> Bloom for Xor16<H>
+where
+    H: Default + BuildHasher + From<Vec<u8>> + Into<Vec<u8>> + Clone,
+{
+    type Err = Error;
+
+    fn add_key<Q: ?Sized + Hash>(&mut self, key: &Q) {
+        self.insert(key)
+    }
+
+    fn add_digest32(&mut self, digest: u32) {
+        self.populate_keys(&[u64::from(digest)])
+    }
+
+    fn build(&mut self) -> Result<()> {
+        err_at!(Fatal, self.build())
+    }
+
+    fn contains<Q: ?Sized + Hash>(&self, element: &Q) -> bool {
+        self.contains(element)
+    }
+
+    fn to_bytes(&self) -> result::Result<Vec<u8>, Self::Err> {
+        let val = CborXor16 {
+            hash_builder: self.hash_builder.clone().into(),
+            seed: self.seed,
+            block_length: self.block_length,
+            finger_prints: self.finger_prints.clone(),
+            keys: self.keys.clone(),
         };
         let cbor_val = err_at!(IOError, val.into_cbor())?;
 
@@ -97,11 +241,41 @@ where
 
     fn from_bytes(mut buf: &[u8]) -> result::Result<(Self, usize), Self::Err> {
         let (val, n) = err_at!(IOError, Cbor::decode(&mut buf))?;
-        Ok((err_at!(IOError, Xor8::<H>::from_cbor(val))?, n))
+        Ok((err_at!(IOError, Xor16::<H>::from_cbor(val))?, n))
     }
 
-    fn or(&self, _other: &Self) -> result::Result<Self, Self::Err> {
-        unimplemented!()
+    fn from_buf<B>(buf: &mut B) -> result::Result<Option<(Self, usize)>, Self::Err>
+    where
+        B: crate::cbor::Buf,
+    {
+        match err_at!(IOError, Cbor::decode_buf(buf))? {
+            None => Ok(None),
+            Some((val, n)) => {
+                let filter = err_at!(IOError, Xor16::<H>::from_cbor(val))?;
+                Ok(Some((filter, n)))
+            }
+        }
+    }
+
+    fn or(&self, other: &Self) -> result::Result<Self, Self::Err> {
+        let (one, two) = match (self.keys.as_ref(), other.keys.as_ref()) {
+            (Some(one), Some(two)) => (one, two),
+            (_, _) => err_at!(
+                Fatal,
+                msg: "cannot merge xor16 filter without retained keys"
+            )?,
+        };
+
+        let mut keys: Vec<u64> = Vec::with_capacity(one.len() + two.len());
+        keys.extend(one.iter().copied());
+        keys.extend(two.iter().copied());
+        keys.sort_unstable();
+        keys.dedup();
+
+        let mut filter = Xor16::<H>::default();
+        filter.populate_keys(&keys);
+        err_at!(Fatal, filter.build())?;
+        Ok(filter)
     }
 }
 
@@ -32,3 +32,108 @@ fn test_basic7() {
         assert!(filter.contains(key), "key {} not present", key);
     }
 }
+
+#[test]
+fn test_or() {
+    use xorfilter::BuildHasherDefault;
+
+    let seed: u128 = random();
+    println!("test_or seed {}", seed);
+    let mut rng = SmallRng::from_seed(seed.to_le_bytes());
+
+    let keys1: Vec<u64> = (0..10_000).map(|_| rng.gen::<u64>()).collect();
+    let keys2: Vec<u64> = (0..10_000).map(|_| rng.gen::<u64>()).collect();
+
+    let mut filter1 = Xor8::<BuildHasherDefault>::new();
+    filter1.populate(&keys1);
+    filter1.build();
+
+    let mut filter2 = Xor8::<BuildHasherDefault>::new();
+    filter2.populate(&keys2);
+    filter2.build();
+
+    let filter = <Xor8<BuildHasherDefault> as Bloom>::or(&filter1, &filter2).unwrap();
+
+    for key in keys1.iter().chain(keys2.iter()) {
+        assert!(filter.contains(key), "key {} not present", key);
+    }
+}
+
+#[test]
+fn test_or_roundtrip_retains_keys() {
+    use xorfilter::BuildHasherDefault;
+
+    let mut filter = Xor8::<BuildHasherDefault>::new();
+    filter.populate(&[1_u64, 2, 3]);
+    filter.build();
+
+    let bytes = <Xor8<BuildHasherDefault> as Bloom>::to_bytes(&filter).unwrap();
+    let (filter, _) = <Xor8<BuildHasherDefault> as Bloom>::from_bytes(&bytes).unwrap();
+
+    assert!(<Xor8<BuildHasherDefault> as Bloom>::or(&filter, &filter).is_ok());
+}
+
+#[test]
+fn test_basic16() {
+    use xorfilter::BuildHasherDefault;
+
+    let seed: u128 = random();
+    println!("test_basic16 seed {}", seed);
+    let mut rng = SmallRng::from_seed(seed.to_le_bytes());
+
+    let keys: Vec<u64> = (0..100_000).map(|_| rng.gen::<u64>()).collect();
+
+    let filter = {
+        let mut filter = Xor16::<BuildHasherDefault>::new();
+        filter.populate(&keys);
+        filter.build();
+        filter
+    };
+
+    for key in keys.iter() {
+        assert!(filter.contains(key), "key {} not present", key);
+    }
+
+    let filter = {
+        let bytes = <Xor16 as Bloom>::to_bytes(&filter).unwrap();
+        <Xor16 as Bloom>::from_bytes(&bytes).unwrap().0
+    };
+
+    for key in keys.iter() {
+        assert!(filter.contains(key), "key {} not present", key);
+    }
+}
+
+#[test]
+fn test_from_buf_incremental() {
+    use xorfilter::BuildHasherDefault;
+
+    let mut filter = Xor8::<BuildHasherDefault>::new();
+    filter.populate(&[1_u64, 2, 3]);
+    filter.build();
+
+    let bytes = <Xor8<BuildHasherDefault> as Bloom>::to_bytes(&filter).unwrap();
+
+    // a truncated buffer must report "need more data" instead of erroring.
+    let mut partial = &bytes[..bytes.len() - 1];
+    assert!(<Xor8<BuildHasherDefault> as Bloom>::from_buf(&mut partial)
+        .unwrap()
+        .is_none());
+
+    let mut full = bytes.as_slice();
+    let (filter, n) = <Xor8<BuildHasherDefault> as Bloom>::from_buf(&mut full)
+        .unwrap()
+        .unwrap();
+    assert_eq!(n, bytes.len());
+    for key in [1_u64, 2, 3].iter() {
+        assert!(filter.contains(key));
+    }
+}
+
+#[test]
+fn test_or_missing_keys_errors() {
+    use xorfilter::BuildHasherDefault;
+
+    let filter: Xor8<BuildHasherDefault> = Xor8::default();
+    assert!(<Xor8<BuildHasherDefault> as Bloom>::or(&filter, &filter).is_err());
+}